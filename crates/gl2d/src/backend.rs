@@ -1,24 +1,510 @@
-use crate::draw_list::DrawContext;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::draw_list::{
+    vert_bytes, BlendMode, ClipOp, Color, DrawCmd, DrawContext, FillRule, Vert,
+};
+use crate::glyph::GlyphCache;
+use crate::gradient::{Gradient, MAX_GRADIENT_STOPS};
+use crate::group::GroupOp;
+use crate::image::Image;
+use crate::shadow::Shadow;
+use crate::texture::{TextureFilter, TextureId, TextureOptions, TextureWrap};
 
 use super::draw_list::DrawList;
 use anyhow::Result;
-use glow::{Buffer, Context, HasContext, UniformLocation, WebFramebufferKey, WebProgramKey};
+use glow::{
+    Buffer, Context, HasContext, UniformLocation, WebFramebufferKey, WebProgramKey,
+    WebRenderbufferKey, WebShaderKey, WebTextureKey, WebVertexArrayKey,
+};
+use nalgebra::{Matrix3, Vector2, Vector4};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 pub struct GlowBackend {
     gl: Context,
-    vbo: Buffer,
-    ebo: Buffer,
+    vao: WebVertexArrayKey,
+    vbo: StreamBuffer,
+    ebo: StreamBuffer,
     fbo: WebFramebufferKey,
+    text_texture: WebTextureKey,
+    text_texture_size: (i32, i32),
     default_material: Material,
+    coverage_material: Material,
     text_material: Material,
+    image_material: Material,
+    blur_material: Material,
+    gradient_material: Material,
+    gradient_text_material: Material,
+    glyph_material: InstancedMaterial,
+    glyph_cover_material: InstancedMaterial,
+    sdf_material: InstancedMaterial,
+    glyph_vbo: Buffer,
+    glyph_ebo: Buffer,
+    inst_vbo: StreamBuffer,
+    quad_vbo: Buffer,
+    sdf_vbo: StreamBuffer,
+    glyph_meshes: Vec<GlyphMesh>,
+    glyph_index_format: IndexFormat,
+    index_format: IndexFormat,
+    textures: HashMap<TextureId, GpuTexture>,
+    next_texture_id: u32,
+    stencil_text: bool,
+    msaa_samples: i32,
+    scene_target: Option<SceneTarget>,
+    offscreen_targets: Vec<OffscreenTarget>,
+    // multisampled buffers groups draw into before resolving to their offscreen target
+    msaa_group_targets: Vec<SceneTarget>,
+    render_targets: HashMap<String, (TextureId, OffscreenTarget)>,
+    // Uploads of the frame in progress, published to `buffer_stats` by `draw`.
+    frame_buffer_stats: BufferStats,
+    buffer_stats: BufferStats,
+    destroyed: bool,
+}
+
+// Per-frame uploads into the streamed vertex, index and instance buffers.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BufferStats {
+    #[wasm_bindgen(js_name = uploadedBytes)]
+    pub uploaded_bytes: usize,
+    // Times a buffer had to grow its storage.
+    pub reallocations: usize,
+    #[wasm_bindgen(js_name = capacityBytes)]
+    pub capacity_bytes: usize,
+}
+
+// Storage is kept across frames and grown geometrically, and each upload goes to the next
+// buffer of a ring so it never waits for the GPU to finish reading the previous one.
+struct StreamBuffer {
+    target: u32,
+    ring: Vec<(Buffer, usize)>,
+    current: usize,
+}
+
+impl StreamBuffer {
+    fn new(gl: &Context, target: u32) -> Result<Self> {
+        let mut buffer = Self {
+            target,
+            ring: vec![],
+            current: 0,
+        };
+        for _ in 0..STREAM_BUFFER_RING {
+            match unsafe { gl.create_buffer() } {
+                Ok(object) => {
+                    track("buffer", 1);
+                    buffer.ring.push((object, 0));
+                }
+                Err(err) => {
+                    buffer.delete(gl);
+                    return Err(glow_error(err));
+                }
+            }
+        }
+        Ok(buffer)
+    }
+
+    fn buffer(&self) -> Buffer {
+        self.ring[self.current].0
+    }
+
+    fn capacity(&self) -> usize {
+        self.ring.iter().map(|&(_, capacity)| capacity).sum()
+    }
+
+    fn delete(&self, gl: &Context) {
+        for &(buffer, _) in &self.ring {
+            unsafe { gl.delete_buffer(buffer) };
+            track("buffer", -1);
+        }
+    }
+
+    // Leaves the buffer holding `data` bound.
+    unsafe fn upload(&mut self, gl: &Context, data: &[u8], stats: &mut BufferStats) {
+        self.current = (self.current + 1) % self.ring.len();
+        let (buffer, capacity) = &mut self.ring[self.current];
+        gl.bind_buffer(self.target, Some(*buffer));
+        if data.is_empty() {
+            return;
+        }
+        if *capacity < data.len() {
+            *capacity = data.len().max(*capacity * 2).max(MIN_STREAM_BUFFER_SIZE);
+            gl.buffer_data_size(self.target, *capacity as i32, glow::DYNAMIC_DRAW);
+            stats.reallocations += 1;
+        }
+        gl.buffer_sub_data_u8_slice(self.target, 0, data);
+        stats.uploaded_bytes += data.len();
+    }
+}
+
+struct SceneTarget {
+    fbo: WebFramebufferKey,
+    color_buffer: WebRenderbufferKey,
+    stencil_buffer: WebRenderbufferKey,
+    width: i32,
+    height: i32,
+    samples: i32,
+}
+
+impl SceneTarget {
+    // With `samples` above zero the buffers are multisampled and `blit` resolves them.
+    // Renderbuffers can't be sampled, so the scene is blitted to a texture before presenting.
+    fn new(gl: &Context, width: i32, height: i32, samples: i32) -> Result<Self> {
+        unsafe {
+            let fbo = gl.create_framebuffer().map_err(glow_error)?;
+            track("framebuffer", 1);
+            let color_buffer = gl.create_renderbuffer().map_err(glow_error)?;
+            track("renderbuffer", 1);
+            let stencil_buffer = gl.create_renderbuffer().map_err(glow_error)?;
+            track("renderbuffer", 1);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, fbo.into());
+            gl.bind_renderbuffer(glow::RENDERBUFFER, color_buffer.into());
+            Self::storage(gl, samples, glow::RGBA8, width, height);
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::RENDERBUFFER,
+                color_buffer.into(),
+            );
+            gl.bind_renderbuffer(glow::RENDERBUFFER, stencil_buffer.into());
+            Self::storage(gl, samples, glow::STENCIL_INDEX8, width, height);
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::STENCIL_ATTACHMENT,
+                glow::RENDERBUFFER,
+                stencil_buffer.into(),
+            );
+            gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            Ok(Self {
+                fbo,
+                color_buffer,
+                stencil_buffer,
+                width,
+                height,
+                samples,
+            })
+        }
+    }
+
+    unsafe fn storage(gl: &Context, samples: i32, format: u32, width: i32, height: i32) {
+        if samples > 0 {
+            gl.renderbuffer_storage_multisample(glow::RENDERBUFFER, samples, format, width, height);
+        } else {
+            gl.renderbuffer_storage(glow::RENDERBUFFER, format, width, height);
+        }
+    }
+
+    // Copies the whole target, so it leaves the scissor test disabled.
+    fn blit(&self, gl: &Context, draw_fbo: WebFramebufferKey) {
+        unsafe {
+            gl.disable(glow::SCISSOR_TEST);
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, self.fbo.into());
+            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, draw_fbo.into());
+            gl.blit_framebuffer(
+                0,
+                0,
+                self.width,
+                self.height,
+                0,
+                0,
+                self.width,
+                self.height,
+                glow::COLOR_BUFFER_BIT,
+                glow::NEAREST,
+            );
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        }
+    }
+
+    fn delete(&self, gl: &Context) {
+        unsafe {
+            gl.delete_framebuffer(self.fbo);
+            gl.delete_renderbuffer(self.color_buffer);
+            gl.delete_renderbuffer(self.stencil_buffer);
+        }
+        track("framebuffer", -1);
+        track("renderbuffer", -2);
+    }
+}
+
+// Target of an open group: drawn into directly, or through multisampled buffers resolved into
+// it when the group ends.
+struct GroupTarget {
+    target: OffscreenTarget,
+    msaa: Option<SceneTarget>,
+}
+
+impl GroupTarget {
+    fn fbo(&self) -> WebFramebufferKey {
+        match &self.msaa {
+            Some(msaa) => msaa.fbo,
+            None => self.target.fbo,
+        }
+    }
+}
+
+// Color texture and stencil buffer the size of the viewport, pooled for groups.
+struct OffscreenTarget {
+    fbo: WebFramebufferKey,
+    texture: WebTextureKey,
+    stencil_buffer: WebRenderbufferKey,
+    width: i32,
+    height: i32,
+}
+
+impl OffscreenTarget {
+    fn new(gl: &Context, width: i32, height: i32) -> Result<Self> {
+        unsafe {
+            let fbo = gl.create_framebuffer().map_err(glow_error)?;
+            track("framebuffer", 1);
+            let texture = gl.create_texture().map_err(glow_error)?;
+            track("texture", 1);
+            let stencil_buffer = gl.create_renderbuffer().map_err(glow_error)?;
+            track("renderbuffer", 1);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, fbo.into());
+            gl.bind_texture(glow::TEXTURE_2D, texture.into());
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA8 as i32,
+                width,
+                height,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                None,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                texture.into(),
+                0,
+            );
+            gl.bind_renderbuffer(glow::RENDERBUFFER, stencil_buffer.into());
+            gl.renderbuffer_storage(glow::RENDERBUFFER, glow::STENCIL_INDEX8, width, height);
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::STENCIL_ATTACHMENT,
+                glow::RENDERBUFFER,
+                stencil_buffer.into(),
+            );
+            gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            Ok(Self {
+                fbo,
+                texture,
+                stencil_buffer,
+                width,
+                height,
+            })
+        }
+    }
+
+    fn delete(&self, gl: &Context) {
+        unsafe {
+            gl.delete_framebuffer(self.fbo);
+            gl.delete_texture(self.texture);
+            gl.delete_renderbuffer(self.stencil_buffer);
+        }
+        track("framebuffer", -1);
+        track("texture", -1);
+        track("renderbuffer", -1);
+    }
 }
 
+struct GpuTexture {
+    texture: WebTextureKey,
+    width: u32,
+    height: u32,
+    mipmaps: bool,
+}
+
+// Indices are uploaded as shorts whenever they can address every vertex.
+#[derive(Clone, Copy)]
+struct IndexFormat {
+    ty: u32,
+}
+
+impl IndexFormat {
+    fn for_vertex_count(count: usize) -> Self {
+        if count <= u16::MAX as usize + 1 {
+            Self {
+                ty: glow::UNSIGNED_SHORT,
+            }
+        } else {
+            Self {
+                ty: glow::UNSIGNED_INT,
+            }
+        }
+    }
+
+    fn offset(self, idx: usize) -> i32 {
+        match self.ty {
+            glow::UNSIGNED_SHORT => (idx * std::mem::size_of::<u16>()) as i32,
+            _ => (idx * std::mem::size_of::<u32>()) as i32,
+        }
+    }
+
+    fn bytes(self, indices: &[u32]) -> Cow<'_, [u8]> {
+        if self.ty == glow::UNSIGNED_SHORT {
+            let bytes = indices
+                .iter()
+                .flat_map(|&idx| (idx as u16).to_ne_bytes())
+                .collect();
+            Cow::Owned(bytes)
+        } else {
+            Cow::Borrowed(bytemuck::cast_slice(indices))
+        }
+    }
+}
+
+struct GlyphMesh {
+    idx_offset: usize,
+    num_of_elems: usize,
+}
+
+struct InstanceBatch {
+    mesh_index: usize,
+    inst_offset: usize,
+    num_of_insts: usize,
+}
+
+impl Drop for GlowBackend {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
+
+// Debug builds count the live GL objects of each kind, so that destroying the last backend can
+// report the ones that were never deleted.
+#[cfg(debug_assertions)]
+thread_local! {
+    static LIVE_OBJECTS: std::cell::RefCell<HashMap<&'static str, i32>> = Default::default();
+}
+
+#[cfg(debug_assertions)]
+fn track(kind: &'static str, count: i32) {
+    LIVE_OBJECTS.with(|live| *live.borrow_mut().entry(kind).or_insert(0) += count);
+}
+
+#[cfg(not(debug_assertions))]
+fn track(_kind: &'static str, _count: i32) {}
+
+#[cfg(debug_assertions)]
+fn report_leaks() {
+    LIVE_OBJECTS.with(|live| {
+        let live = live.borrow();
+        if live.get("backend").copied().unwrap_or(0) > 0 {
+            return;
+        }
+        for (kind, &count) in live.iter() {
+            if count != 0 {
+                crate::log!("gl2d: {} {} object(s) leaked", count, kind);
+            }
+        }
+    });
+}
+
+#[cfg(not(debug_assertions))]
+fn report_leaks() {}
+
 fn glow_error(s: String) -> anyhow::Error {
     anyhow::anyhow!("Glow Error: {}", s)
 }
 
+enum GlObject {
+    VertexArray(WebVertexArrayKey),
+    Program(WebProgramKey),
+    Buffer(Buffer),
+    Framebuffer(WebFramebufferKey),
+    Texture(WebTextureKey),
+}
+
+// The GL objects `GlowBackend::new` created so far, deleted when a later step fails.
+struct CreatedObjects<'a> {
+    gl: &'a Context,
+    objects: Vec<GlObject>,
+}
+
+impl<'a> CreatedObjects<'a> {
+    fn new(gl: &'a Context) -> Self {
+        Self {
+            gl,
+            objects: vec![],
+        }
+    }
+
+    fn add(&mut self, object: GlObject) {
+        self.objects.push(object);
+    }
+
+    fn add_material(&mut self, material: Material) -> Material {
+        self.add(GlObject::Program(material.program));
+        material
+    }
+
+    fn add_instanced_material(&mut self, material: InstancedMaterial) -> InstancedMaterial {
+        self.add(GlObject::Program(material.material.program));
+        material
+    }
+
+    fn add_stream_buffer(&mut self, buffer: StreamBuffer) -> StreamBuffer {
+        for &(object, _) in &buffer.ring {
+            self.add(GlObject::Buffer(object));
+        }
+        buffer
+    }
+
+    // Hands the objects over to the backend.
+    fn keep(mut self) {
+        self.objects.clear();
+    }
+}
+
+impl Drop for CreatedObjects<'_> {
+    fn drop(&mut self) {
+        let gl = self.gl;
+        for object in self.objects.drain(..).rev() {
+            unsafe {
+                match object {
+                    GlObject::VertexArray(vao) => {
+                        gl.delete_vertex_array(vao);
+                        track("vertex array", -1);
+                    }
+                    GlObject::Program(program) => {
+                        gl.delete_program(program);
+                        track("program", -1);
+                    }
+                    GlObject::Buffer(buffer) => {
+                        gl.delete_buffer(buffer);
+                        track("buffer", -1);
+                    }
+                    GlObject::Framebuffer(fbo) => {
+                        gl.delete_framebuffer(fbo);
+                        track("framebuffer", -1);
+                    }
+                    GlObject::Texture(texture) => {
+                        gl.delete_texture(texture);
+                        track("texture", -1);
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[wasm_bindgen]
 impl GlowBackend {
     #[wasm_bindgen(constructor)]
@@ -26,31 +512,215 @@ impl GlowBackend {
         let gl = glow::Context::from_webgl2_context(webgl_context);
         Self::new(gl).unwrap()
     }
+
+    #[wasm_bindgen(getter = stencilText)]
+    pub fn stencil_text(&self) -> bool {
+        self.stencil_text
+    }
+
+    #[wasm_bindgen(setter = stencilText)]
+    pub fn set_stencil_text(&mut self, stencil_text: bool) {
+        self.stencil_text = stencil_text;
+    }
+
+    // Uploads of the last frame drawn, including any `render_to_texture` calls before it.
+    #[wasm_bindgen(getter = bufferStats)]
+    pub fn buffer_stats(&self) -> BufferStats {
+        self.buffer_stats
+    }
+
+    fn check_alive(&self) -> Result<()> {
+        if self.destroyed {
+            return Err(anyhow::anyhow!("The backend was destroyed"));
+        }
+        Ok(())
+    }
+
+    // Deletes every GL object of the backend, which can't draw afterwards. Dropping the backend
+    // does the same.
+    pub fn destroy(&mut self) {
+        if self.destroyed {
+            return;
+        }
+        self.destroyed = true;
+        let gl = &self.gl;
+        for buffer in [&self.vbo, &self.ebo, &self.inst_vbo, &self.sdf_vbo] {
+            buffer.delete(gl);
+        }
+        for material in [
+            &self.default_material,
+            &self.coverage_material,
+            &self.text_material,
+            &self.image_material,
+            &self.blur_material,
+            &self.gradient_material,
+            &self.gradient_text_material,
+            &self.glyph_material.material,
+            &self.glyph_cover_material.material,
+            &self.sdf_material.material,
+        ] {
+            material.delete(gl);
+        }
+        unsafe {
+            for &buffer in &[self.glyph_vbo, self.glyph_ebo, self.quad_vbo] {
+                gl.delete_buffer(buffer);
+                track("buffer", -1);
+            }
+            gl.delete_framebuffer(self.fbo);
+            track("framebuffer", -1);
+            gl.delete_texture(self.text_texture);
+            track("texture", -1);
+            gl.bind_vertex_array(None);
+            gl.delete_vertex_array(self.vao);
+            track("vertex array", -1);
+        }
+        // render targets own their textures
+        for (_, (id, target)) in self.render_targets.drain() {
+            self.textures.remove(&id);
+            target.delete(gl);
+        }
+        for (_, texture) in self.textures.drain() {
+            unsafe { gl.delete_texture(texture.texture) };
+            track("texture", -1);
+        }
+        for target in self.offscreen_targets.drain(..) {
+            target.delete(gl);
+        }
+        for target in self.msaa_group_targets.drain(..) {
+            target.delete(gl);
+        }
+        if let Some(target) = self.scene_target.take() {
+            target.delete(gl);
+        }
+        track("backend", -1);
+        report_leaks();
+    }
+
+    #[wasm_bindgen(getter = msaaSamples)]
+    pub fn msaa_samples(&self) -> i32 {
+        self.msaa_samples
+    }
+
+    // 0 disables multisampling; larger values are clamped to what the context supports. Groups
+    // are multisampled too, but text layers outside of stencil text count their coverage in a
+    // single-sampled texture.
+    #[wasm_bindgen(setter = msaaSamples)]
+    pub fn set_msaa_samples(&mut self, msaa_samples: i32) {
+        let max_samples = unsafe { self.gl.get_parameter_i32(glow::MAX_SAMPLES) };
+        self.msaa_samples = msaa_samples.max(0).min(max_samples);
+    }
 }
 
 impl GlowBackend {
     pub fn new(gl: Context) -> Result<Self> {
         unsafe {
+            let mut created = CreatedObjects::new(&gl);
             let vao = gl.create_vertex_array().map_err(glow_error)?;
+            track("vertex array", 1);
+            created.add(GlObject::VertexArray(vao));
             gl.bind_vertex_array(Some(vao));
             gl.enable(glow::BLEND);
             gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
-            let default_material = Material::new(
+            let default_material = created.add_material(Material::new(
                 &gl,
                 include_str!("shaders/default.vert"),
                 include_str!("shaders/default.frag"),
-            )?;
-            let text_material = Material::new(
+            )?);
+            let coverage_material = created.add_material(Material::new(
+                &gl,
+                include_str!("shaders/default.vert"),
+                include_str!("shaders/coverage.frag"),
+            )?);
+            let text_material = created.add_material(Material::new(
                 &gl,
                 include_str!("shaders/text.vert"),
                 include_str!("shaders/text.frag"),
-            )?;
-            let vbo = gl.create_buffer().map_err(glow_error)?;
-            let ebo = gl.create_buffer().map_err(glow_error)?;
+            )?);
+            let image_material = created.add_material(Material::new(
+                &gl,
+                include_str!("shaders/image.vert"),
+                include_str!("shaders/image.frag"),
+            )?);
+            let blur_material = created.add_material(Material::new(
+                &gl,
+                include_str!("shaders/image.vert"),
+                include_str!("shaders/blur.frag"),
+            )?);
+            let gradient_material = created.add_material(Material::new(
+                &gl,
+                include_str!("shaders/gradient.vert"),
+                concat!(
+                    include_str!("shaders/gradient.glsl"),
+                    include_str!("shaders/gradient.frag")
+                ),
+            )?);
+            let gradient_text_material = created.add_material(Material::new(
+                &gl,
+                include_str!("shaders/gradient_text.vert"),
+                concat!(
+                    include_str!("shaders/gradient.glsl"),
+                    include_str!("shaders/gradient_text.frag")
+                ),
+            )?);
+            let glyph_material = created.add_instanced_material(InstancedMaterial::new(
+                &gl,
+                include_str!("shaders/glyph_instanced.vert"),
+                include_str!("shaders/coverage.frag"),
+                GLYPH_INSTANCE_ATTRIBUTES,
+            )?);
+            let glyph_cover_material = created.add_instanced_material(InstancedMaterial::new(
+                &gl,
+                concat!(
+                    "#define INSTANCE_COLOR\n",
+                    include_str!("shaders/glyph_instanced.vert")
+                ),
+                include_str!("shaders/default.frag"),
+                GLYPH_INSTANCE_ATTRIBUTES,
+            )?);
+            let sdf_material = created.add_instanced_material(InstancedMaterial::new(
+                &gl,
+                include_str!("shaders/sdf.vert"),
+                include_str!("shaders/sdf.frag"),
+                &[
+                    ("inst_center", 2),
+                    ("inst_axis", 2),
+                    ("inst_half_size", 2),
+                    ("inst_radii", 4),
+                    ("inst_thickness", 1),
+                    ("inst_color", 4),
+                ],
+            )?);
+            let vbo = created.add_stream_buffer(StreamBuffer::new(&gl, glow::ARRAY_BUFFER)?);
+            let ebo =
+                created.add_stream_buffer(StreamBuffer::new(&gl, glow::ELEMENT_ARRAY_BUFFER)?);
+            let glyph_vbo = gl.create_buffer().map_err(glow_error)?;
+            track("buffer", 1);
+            created.add(GlObject::Buffer(glyph_vbo));
+            let glyph_ebo = gl.create_buffer().map_err(glow_error)?;
+            track("buffer", 1);
+            created.add(GlObject::Buffer(glyph_ebo));
+            let inst_vbo = created.add_stream_buffer(StreamBuffer::new(&gl, glow::ARRAY_BUFFER)?);
+            let sdf_vbo = created.add_stream_buffer(StreamBuffer::new(&gl, glow::ARRAY_BUFFER)?);
+            let quad_vbo = gl.create_buffer().map_err(glow_error)?;
+            track("buffer", 1);
+            created.add(GlObject::Buffer(quad_vbo));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(quad_vbo));
+            let white = Color::new(1., 1., 1., 1.);
+            let quad = [
+                Vert::new(Vector4::new(-1., -1., 0., 0.), white),
+                Vert::new(Vector4::new(1., -1., 1., 0.), white),
+                Vert::new(Vector4::new(-1., 1., 0., 1.), white),
+                Vert::new(Vector4::new(1., 1., 1., 1.), white),
+            ];
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, vert_bytes(&quad), glow::STATIC_DRAW);
             let fbo = gl.create_framebuffer().map_err(glow_error)?;
+            track("framebuffer", 1);
+            created.add(GlObject::Framebuffer(fbo));
             gl.bind_framebuffer(glow::FRAMEBUFFER, fbo.into());
-            let color_buffer = gl.create_texture().map_err(glow_error)?;
-            gl.bind_texture(glow::TEXTURE_2D, color_buffer.into());
+            let text_texture = gl.create_texture().map_err(glow_error)?;
+            track("texture", 1);
+            created.add(GlObject::Texture(text_texture));
+            gl.bind_texture(glow::TEXTURE_2D, text_texture.into());
             gl.tex_parameter_i32(
                 glow::TEXTURE_2D,
                 glow::TEXTURE_MAG_FILTER,
@@ -65,22 +735,740 @@ impl GlowBackend {
                 glow::FRAMEBUFFER,
                 glow::COLOR_ATTACHMENT0,
                 glow::TEXTURE_2D,
-                color_buffer.into(),
+                text_texture.into(),
                 0,
             );
             gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            created.keep();
+            track("backend", 1);
             Ok(Self {
                 gl,
+                vao,
                 vbo,
                 ebo,
                 fbo,
+                text_texture,
+                text_texture_size: (0, 0),
                 default_material,
+                coverage_material,
                 text_material,
+                image_material,
+                blur_material,
+                gradient_material,
+                gradient_text_material,
+                glyph_material,
+                glyph_cover_material,
+                sdf_material,
+                glyph_vbo,
+                glyph_ebo,
+                inst_vbo,
+                quad_vbo,
+                sdf_vbo,
+                glyph_meshes: vec![],
+                glyph_index_format: IndexFormat::for_vertex_count(0),
+                index_format: IndexFormat::for_vertex_count(0),
+                textures: HashMap::new(),
+                next_texture_id: 0,
+                stencil_text: false,
+                msaa_samples: 0,
+                scene_target: None,
+                offscreen_targets: vec![],
+                msaa_group_targets: vec![],
+                render_targets: HashMap::new(),
+                frame_buffer_stats: BufferStats::default(),
+                buffer_stats: BufferStats::default(),
+                destroyed: false,
             })
         }
     }
 
-    pub fn draw(&mut self, draw_context: &DrawContext, draw_list: &DrawList) -> Result<()> {
+    pub fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        options: TextureOptions,
+    ) -> Result<TextureId> {
+        self.check_alive()?;
+        // checked before anything is allocated
+        check_pixels_len(width, height, pixels)?;
+        unsafe {
+            let texture = self.gl.create_texture().map_err(glow_error)?;
+            track("texture", 1);
+            self.gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            for &(pname, param) in &[
+                (
+                    glow::TEXTURE_MIN_FILTER,
+                    min_filter_param(options.min_filter, options.mipmaps),
+                ),
+                (glow::TEXTURE_MAG_FILTER, filter_param(options.mag_filter)),
+                (glow::TEXTURE_WRAP_S, wrap_param(options.wrap)),
+                (glow::TEXTURE_WRAP_T, wrap_param(options.wrap)),
+            ] {
+                self.gl
+                    .tex_parameter_i32(glow::TEXTURE_2D, pname, param as i32);
+            }
+            let id = TextureId(self.next_texture_id);
+            self.next_texture_id += 1;
+            self.textures.insert(
+                id,
+                GpuTexture {
+                    texture,
+                    width,
+                    height,
+                    mipmaps: options.mipmaps,
+                },
+            );
+            self.update_texture(id, pixels)?;
+            Ok(id)
+        }
+    }
+
+    pub fn create_texture_from_image(
+        &mut self,
+        image: &Image,
+        options: TextureOptions,
+    ) -> Result<TextureId> {
+        self.create_texture(image.width, image.height, &image.pixels, options)
+    }
+
+    // Decodes PNG or JPEG data into a new texture.
+    pub fn load_image(&mut self, bytes: &[u8], options: TextureOptions) -> Result<TextureId> {
+        let image = Image::decode(bytes)?;
+        self.create_texture_from_image(&image, options)
+    }
+
+    // `pixels` are tightly packed, straight RGBA8 rows covering the whole texture.
+    pub fn update_texture(&mut self, id: TextureId, pixels: &[u8]) -> Result<()> {
+        let texture = self
+            .textures
+            .get(&id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown texture {:?}", id))?;
+        check_pixels_len(texture.width, texture.height, pixels)?;
+        let mut image = Image {
+            width: texture.width,
+            height: texture.height,
+            pixels: pixels.to_vec(),
+        };
+        image.premultiply();
+        let levels = if texture.mipmaps {
+            image.mip_levels()
+        } else {
+            vec![image]
+        };
+        unsafe {
+            self.gl
+                .bind_texture(glow::TEXTURE_2D, Some(texture.texture));
+            // same size, so the levels allocated by `create_texture` are written in place
+            for (level, image) in levels.iter().enumerate() {
+                self.gl.tex_sub_image_2d(
+                    glow::TEXTURE_2D,
+                    level as i32,
+                    0,
+                    0,
+                    image.width as i32,
+                    image.height as i32,
+                    glow::RGBA,
+                    glow::UNSIGNED_BYTE,
+                    glow::PixelUnpackData::Slice(&image.pixels),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn delete_texture(&mut self, id: TextureId) {
+        let name = self
+            .render_targets
+            .iter()
+            .find(|(_, (target_id, _))| *target_id == id)
+            .map(|(name, _)| name.clone());
+        if let Some((_, target)) = name.and_then(|name| self.render_targets.remove(&name)) {
+            // the target owns the texture
+            self.textures.remove(&id);
+            target.delete(&self.gl);
+        } else if let Some(texture) = self.textures.remove(&id) {
+            unsafe { self.gl.delete_texture(texture.texture) };
+            track("texture", -1);
+        }
+    }
+
+    // Renders `draw_list` into the texture named `name`, at `screen_size * pixel_ratio` of
+    // `draw_context`, for drawing later with `DrawList::add_image`. Rendering the same name again
+    // reuses the texture and keeps its id. Groups left open in `draw_list` are ended.
+    pub fn render_to_texture(
+        &mut self,
+        name: &str,
+        draw_context: &DrawContext,
+        draw_list: &mut DrawList,
+    ) -> Result<TextureId> {
+        self.check_alive()?;
+        draw_list.end_all_groups();
+        let (width, height) = viewport_size(draw_context);
+        let (id, fbo) = match self.render_targets.get(name) {
+            Some((id, target)) if target.width == width && target.height == height => {
+                (*id, target.fbo)
+            }
+            _ => {
+                let id = match self.render_targets.remove(name) {
+                    Some((id, target)) => {
+                        target.delete(&self.gl);
+                        id
+                    }
+                    None => {
+                        let id = TextureId(self.next_texture_id);
+                        self.next_texture_id += 1;
+                        id
+                    }
+                };
+                let target = OffscreenTarget::new(&self.gl, width, height)?;
+                unsafe {
+                    self.gl.bind_texture(glow::TEXTURE_2D, Some(target.texture));
+                    for &pname in &[glow::TEXTURE_MIN_FILTER, glow::TEXTURE_MAG_FILTER] {
+                        self.gl
+                            .tex_parameter_i32(glow::TEXTURE_2D, pname, glow::LINEAR as i32);
+                    }
+                }
+                self.textures.insert(
+                    id,
+                    GpuTexture {
+                        texture: target.texture,
+                        width: width as u32,
+                        height: height as u32,
+                        mipmaps: false,
+                    },
+                );
+                let fbo = target.fbo;
+                self.render_targets.insert(name.to_string(), (id, target));
+                (id, fbo)
+            }
+        };
+        self.render(draw_context, draw_list, Some(fbo), true)?;
+        Ok(id)
+    }
+
+    pub fn texture_size(&self, id: TextureId) -> Option<(u32, u32)> {
+        self.textures
+            .get(&id)
+            .map(|texture| (texture.width, texture.height))
+    }
+
+    pub fn upload_glyphs(&mut self, glyph_cache: &GlyphCache) -> Result<()> {
+        self.check_alive()?;
+        let mut vertices: Vec<Vert> = vec![];
+        let mut indices = vec![];
+        self.glyph_meshes.clear();
+        glyph_cache.set_uploaded(glyph_cache.glyphs().len());
+        for glyph in glyph_cache.glyphs() {
+            let vtx_offset = vertices.len() as u32;
+            self.glyph_meshes.push(GlyphMesh {
+                idx_offset: indices.len(),
+                num_of_elems: glyph.num_of_elems(),
+            });
+            indices.extend(glyph.indices().iter().map(|idx| idx + vtx_offset));
+            vertices.extend_from_slice(glyph.vertices());
+        }
+        self.glyph_index_format = IndexFormat::for_vertex_count(vertices.len());
+        unsafe {
+            self.gl
+                .bind_buffer(glow::ARRAY_BUFFER, Some(self.glyph_vbo));
+            self.gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                vert_bytes(&vertices),
+                glow::STATIC_DRAW,
+            );
+            self.gl
+                .bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.glyph_ebo));
+            self.gl.buffer_data_u8_slice(
+                glow::ELEMENT_ARRAY_BUFFER,
+                &self.glyph_index_format.bytes(&indices),
+                glow::STATIC_DRAW,
+            );
+        }
+        Ok(())
+    }
+
+    fn upload_instances(&mut self, draw_list: &DrawList) -> Vec<Vec<InstanceBatch>> {
+        let mut instances: Vec<f32> = vec![];
+        let mut batches = Vec::with_capacity(draw_list.cmds.len());
+        for cmd in &draw_list.cmds {
+            let mut cmd_instances =
+                draw_list.inst_buffer[cmd.inst_offset..cmd.inst_offset + cmd.num_of_insts].to_vec();
+            cmd_instances.sort_by_key(|(mesh_index, _)| *mesh_index);
+            let mut cmd_batches: Vec<InstanceBatch> = vec![];
+            for (mesh_index, inst) in cmd_instances {
+                match cmd_batches.last_mut() {
+                    Some(batch) if batch.mesh_index == mesh_index => batch.num_of_insts += 1,
+                    _ => cmd_batches.push(InstanceBatch {
+                        mesh_index,
+                        inst_offset: instances.len() / INSTANCE_FLOATS,
+                        num_of_insts: 1,
+                    }),
+                }
+                // column-major 2x3: x axis, y axis, offset
+                instances.extend(inst.transform.matrix().fixed_slice::<2, 3>(0, 0).iter());
+                instances.extend(inst.col.iter().map(|&c| c as f32 / 255.));
+            }
+            batches.push(cmd_batches);
+        }
+        if !instances.is_empty() {
+            unsafe {
+                self.inst_vbo.upload(
+                    &self.gl,
+                    bytemuck::cast_slice(&instances),
+                    &mut self.frame_buffer_stats,
+                );
+            }
+        }
+        batches
+    }
+
+    unsafe fn prepare_gradient(
+        &self,
+        material: &Material,
+        projection: &[f32; 16],
+        gradient: &Gradient,
+        to_world: &Matrix3<f32>,
+    ) {
+        material.prepare(&self.gl, projection);
+        let transform = gradient.world_to_gradient() * to_world;
+        self.gl.uniform_matrix_3_f32_slice(
+            material.uniform("gradient_transform"),
+            false,
+            transform.as_slice(),
+        );
+        self.gl
+            .uniform_1_i32(material.uniform("gradient_kind"), gradient.kind_index());
+        self.gl
+            .uniform_1_i32(material.uniform("gradient_spread"), gradient.spread_index());
+        let stops = &gradient.stops[..gradient.stops.len().min(MAX_GRADIENT_STOPS)];
+        self.gl
+            .uniform_1_i32(material.uniform("stop_count"), stops.len() as i32);
+        if !stops.is_empty() {
+            let offsets: Vec<f32> = stops.iter().map(|&(offset, _)| offset).collect();
+            // premultiplied, so stops blend into transparent ones without darkening
+            let colors: Vec<f32> = stops
+                .iter()
+                .flat_map(|(_, col)| [col.x * col.w, col.y * col.w, col.z * col.w, col.w])
+                .collect();
+            self.gl
+                .uniform_1_f32_slice(material.uniform("stop_offsets"), &offsets);
+            self.gl
+                .uniform_4_f32_slice(material.uniform("stop_colors"), &colors);
+        }
+    }
+
+    fn upload_sdf_shapes(&mut self, draw_list: &DrawList) {
+        if draw_list.sdf_buffer.is_empty() {
+            return;
+        }
+        let mut instances: Vec<f32> = Vec::with_capacity(draw_list.sdf_buffer.len() * SDF_FLOATS);
+        for shape in &draw_list.sdf_buffer {
+            instances.extend(shape.center.iter());
+            instances.extend(shape.axis.iter());
+            instances.extend(shape.half_size.iter());
+            instances.extend(shape.radii.iter());
+            instances.push(shape.thickness);
+            instances.extend(shape.col.iter());
+        }
+        unsafe {
+            self.sdf_vbo.upload(
+                &self.gl,
+                bytemuck::cast_slice(&instances),
+                &mut self.frame_buffer_stats,
+            );
+        }
+    }
+
+    // Texture coordinates are unpacked as `uv_offset + uv * uv_scale`.
+    unsafe fn prepare_image(
+        &self,
+        projection: &[f32; 16],
+        offset: Vector2<f32>,
+        scale: Vector2<f32>,
+    ) {
+        let material = &self.image_material;
+        material.prepare(&self.gl, projection);
+        self.gl
+            .uniform_2_f32(material.uniform("uv_offset"), offset.x, offset.y);
+        self.gl
+            .uniform_2_f32(material.uniform("uv_scale"), scale.x, scale.y);
+    }
+
+    unsafe fn draw_sdf_shapes(&self, projection: &[f32; 16], pixel_size: f32, cmd: &DrawCmd) {
+        self.gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.quad_vbo));
+        self.sdf_material.prepare(&self.gl, projection);
+        self.gl
+            .uniform_1_f32(self.sdf_material.uniform("pixel_size"), pixel_size);
+        self.gl
+            .bind_buffer(glow::ARRAY_BUFFER, Some(self.sdf_vbo.buffer()));
+        self.sdf_material
+            .prepare_instances(&self.gl, cmd.sdf_offset);
+        self.gl
+            .draw_arrays_instanced(glow::TRIANGLE_STRIP, 0, 4, cmd.num_of_sdfs as i32);
+        self.sdf_material.finish_instances(&self.gl);
+        self.gl
+            .bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo.buffer()));
+        self.default_material.prepare(&self.gl, projection);
+    }
+
+    unsafe fn draw_instances(
+        &self,
+        material: &InstancedMaterial,
+        projection: &[f32; 16],
+        batches: &[InstanceBatch],
+    ) {
+        self.gl
+            .bind_buffer(glow::ARRAY_BUFFER, Some(self.glyph_vbo));
+        material.prepare(&self.gl, projection);
+        self.gl
+            .bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.glyph_ebo));
+        self.gl
+            .bind_buffer(glow::ARRAY_BUFFER, Some(self.inst_vbo.buffer()));
+        for batch in batches {
+            // meshes of a cache uploaded to another backend
+            let mesh = match self.glyph_meshes.get(batch.mesh_index) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            material.prepare_instances(&self.gl, batch.inst_offset);
+            self.gl.draw_elements_instanced(
+                glow::TRIANGLES,
+                (mesh.num_of_elems * 3) as i32,
+                self.glyph_index_format.ty,
+                self.glyph_index_format.offset(mesh.idx_offset),
+                batch.num_of_insts as i32,
+            );
+        }
+        material.finish_instances(&self.gl);
+        self.gl
+            .bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo.buffer()));
+        self.gl
+            .bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.ebo.buffer()));
+    }
+
+    // Text layers count their windings in the bottom left `width` x `height` of this texture,
+    // cleared by each layer. It only grows, so rendering at several sizes doesn't reallocate it.
+    fn fit_text_texture(&mut self, width: i32, height: i32) {
+        let (old_width, old_height) = self.text_texture_size;
+        if width > old_width || height > old_height {
+            let (width, height) = (width.max(old_width), height.max(old_height));
+            unsafe {
+                self.gl
+                    .bind_texture(glow::TEXTURE_2D, Some(self.text_texture));
+                self.gl.tex_image_2d(
+                    glow::TEXTURE_2D,
+                    0,
+                    glow::RGBA as i32,
+                    width,
+                    height,
+                    0,
+                    glow::RGBA,
+                    glow::UNSIGNED_BYTE,
+                    None,
+                );
+            }
+            self.text_texture_size = (width, height);
+        }
+        let (texture_width, texture_height) = self.text_texture_size;
+        let coord_scale = (
+            width as f32 / texture_width as f32,
+            height as f32 / texture_height as f32,
+        );
+        for material in [&self.text_material, &self.gradient_text_material] {
+            unsafe {
+                self.gl.use_program(Some(material.program));
+                self.gl.uniform_2_f32(
+                    material.uniform("coord_scale"),
+                    coord_scale.0,
+                    coord_scale.1,
+                );
+            }
+        }
+    }
+
+    // Clip paths need the stencil buffer of a scene target, like stencil text.
+    fn update_scene_target(&mut self, width: i32, height: i32, stencil: bool) -> Result<()> {
+        let needs_target = self.stencil_text || self.msaa_samples > 0 || stencil;
+        let up_to_date = match &self.scene_target {
+            Some(target) => {
+                needs_target
+                    && target.width == width
+                    && target.height == height
+                    && target.samples == self.msaa_samples
+            }
+            None => !needs_target,
+        };
+        if up_to_date {
+            return Ok(());
+        }
+        if let Some(target) = self.scene_target.take() {
+            target.delete(&self.gl);
+        }
+        if needs_target {
+            self.scene_target = Some(SceneTarget::new(
+                &self.gl,
+                width,
+                height,
+                self.msaa_samples,
+            )?);
+        }
+        Ok(())
+    }
+
+    // Takes a pooled target of the given size, or makes one.
+    fn acquire_offscreen_target(&mut self, width: i32, height: i32) -> Result<OffscreenTarget> {
+        let pooled = self
+            .offscreen_targets
+            .iter()
+            .rposition(|target| target.width == width && target.height == height);
+        match pooled {
+            Some(i) => Ok(self.offscreen_targets.remove(i)),
+            None => OffscreenTarget::new(&self.gl, width, height),
+        }
+    }
+
+    // Takes pooled multisampled buffers of the given size and the current sample count, or
+    // makes them.
+    fn acquire_msaa_group_target(&mut self, width: i32, height: i32) -> Result<SceneTarget> {
+        let samples = self.msaa_samples;
+        let pooled = self.msaa_group_targets.iter().rposition(|target| {
+            target.width == width && target.height == height && target.samples == samples
+        });
+        match pooled {
+            Some(i) => Ok(self.msaa_group_targets.remove(i)),
+            None => SceneTarget::new(&self.gl, width, height, samples),
+        }
+    }
+
+    fn release_group_target(&mut self, group: GroupTarget) {
+        if let Some(msaa) = group.msaa {
+            self.msaa_group_targets.push(msaa);
+            if self.msaa_group_targets.len() > MAX_POOLED_TARGETS {
+                self.msaa_group_targets.remove(0).delete(&self.gl);
+            }
+        }
+        self.release_offscreen_target(group.target);
+    }
+
+    // Pools `target` for later groups, of this size or others, deleting the least recently
+    // used targets past `MAX_POOLED_TARGETS`.
+    fn release_offscreen_target(&mut self, target: OffscreenTarget) {
+        self.offscreen_targets.push(target);
+        if self.offscreen_targets.len() > MAX_POOLED_TARGETS {
+            self.offscreen_targets.remove(0).delete(&self.gl);
+        }
+    }
+
+    // Blurs the alpha of `source` horizontally into a pooled target, then vertically into
+    // `parent_fbo`, shifted by the shadow offset. `px` is the number of pixels per world unit.
+    unsafe fn draw_shadow(
+        &mut self,
+        shadow: &Shadow,
+        px: f32,
+        source: &OffscreenTarget,
+        cmd: &DrawCmd,
+        parent_fbo: Option<WebFramebufferKey>,
+        flip_y: bool,
+    ) -> Result<()> {
+        let (width, height) = (source.width, source.height);
+        let radius = shadow.radius * px;
+        let blurred = self.acquire_offscreen_target(width, height)?;
+        let quad_offset = self.index_format.offset(cmd.idx_offset);
+
+        // the first pass covers the whole target, whatever the clip of the group
+        self.gl.disable(glow::SCISSOR_TEST);
+        self.gl.disable(glow::STENCIL_TEST);
+        self.gl
+            .bind_framebuffer(glow::FRAMEBUFFER, Some(blurred.fbo));
+        self.gl.clear_color(0., 0., 0., 0.);
+        self.gl.clear(glow::COLOR_BUFFER_BIT);
+        self.gl.blend_func(glow::ONE, glow::ZERO);
+        let material = &self.blur_material;
+        material.prepare(&self.gl, &IDENTITY);
+        // image.vert unpacks the UVs, which the blur quads use as they are
+        self.gl.uniform_2_f32(material.uniform("uv_offset"), 0., 0.);
+        self.gl.uniform_2_f32(material.uniform("uv_scale"), 1., 1.);
+        let col = shadow.col;
+        self.gl.uniform_4_f32(
+            material.uniform("color"),
+            col.x * col.w,
+            col.y * col.w,
+            col.z * col.w,
+            col.w,
+        );
+        self.gl.uniform_1_f32(
+            material.uniform("tap_spacing"),
+            (radius / BLUR_TAPS).max(1.),
+        );
+        self.gl
+            .uniform_1_f32(material.uniform("sigma"), (radius * 0.5).max(1e-3));
+        self.gl
+            .uniform_2_f32(material.uniform("direction"), 1. / width as f32, 0.);
+        self.gl.uniform_1_i32(material.uniform("alpha_only"), 1);
+        self.gl.bind_texture(glow::TEXTURE_2D, Some(source.texture));
+        self.gl
+            .draw_elements(glow::TRIANGLES, 6, self.index_format.ty, quad_offset);
+
+        self.gl.bind_framebuffer(glow::FRAMEBUFFER, parent_fbo);
+        if cmd.clip_rect.is_some() {
+            self.gl.enable(glow::SCISSOR_TEST);
+        }
+        self.apply_clip_depth(cmd.clip_depth);
+        self.apply_blend_mode(cmd.blend_mode);
+        let mut projection = IDENTITY;
+        projection[12] = 2. * shadow.offset.x * px / width as f32;
+        projection[13] = -2. * shadow.offset.y * px / height as f32;
+        if flip_y {
+            projection[13] = -projection[13];
+        }
+        material.prepare(&self.gl, &projection);
+        self.gl
+            .uniform_2_f32(material.uniform("direction"), 0., 1. / height as f32);
+        self.gl.uniform_1_i32(material.uniform("alpha_only"), 0);
+        self.gl
+            .bind_texture(glow::TEXTURE_2D, Some(blurred.texture));
+        self.gl
+            .draw_elements(glow::TRIANGLES, 6, self.index_format.ty, quad_offset);
+        self.release_offscreen_target(blurred);
+        Ok(())
+    }
+
+    unsafe fn apply_blend_mode(&self, blend_mode: BlendMode) {
+        let (src_factor, dst_factor) = blend_factors(blend_mode);
+        // alpha always composites over, leaving offscreen targets premultiplied
+        self.gl
+            .blend_func_separate(src_factor, dst_factor, glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
+    }
+
+    // Scissors to the clip rect of `cmd`. Clip masks are never scissored, so that pops undo
+    // their pushes entirely.
+    unsafe fn apply_clip_rect(
+        &self,
+        draw_context: &DrawContext,
+        cmd: &DrawCmd,
+        height: i32,
+        flip_y: bool,
+    ) {
+        match (cmd.clip_rect, cmd.clip_op) {
+            (Some((min, max)), None) => {
+                let [x, y, w, h] = scissor_rect(draw_context, min, max, height, flip_y);
+                self.gl.enable(glow::SCISSOR_TEST);
+                self.gl.scissor(x, y, w, h);
+            }
+            _ => self.gl.disable(glow::SCISSOR_TEST),
+        }
+    }
+
+    // Restricts drawing to the pixels inside all of the `depth` innermost clip paths.
+    unsafe fn apply_clip_depth(&self, depth: usize) {
+        if depth > 0 {
+            self.gl.enable(glow::STENCIL_TEST);
+            self.gl
+                .stencil_func(glow::EQUAL, clip_level(depth), CLIP_MASK);
+            self.gl.stencil_op(glow::KEEP, glow::KEEP, glow::KEEP);
+        } else {
+            self.gl.disable(glow::STENCIL_TEST);
+        }
+    }
+
+    // Accumulates the winding of the layer's coverage triangles in the low stencil bits, only
+    // where the clip level matches the layer's.
+    unsafe fn draw_stencil_coverage(
+        &self,
+        projection: &[f32; 16],
+        cmd: &DrawCmd,
+        batches: &[InstanceBatch],
+    ) {
+        self.gl.enable(glow::STENCIL_TEST);
+        self.gl.color_mask(false, false, false, false);
+        self.gl.stencil_mask(WINDING_MASK);
+        self.gl
+            .stencil_func(glow::EQUAL, clip_level(cmd.clip_depth), CLIP_MASK);
+        match cmd.fill_rule {
+            FillRule::EvenOdd => {
+                self.gl.stencil_op(glow::KEEP, glow::KEEP, glow::INVERT);
+            }
+            FillRule::NonZero => {
+                self.gl
+                    .stencil_op_separate(glow::FRONT, glow::KEEP, glow::KEEP, glow::INCR_WRAP);
+                self.gl
+                    .stencil_op_separate(glow::BACK, glow::KEEP, glow::KEEP, glow::DECR_WRAP);
+            }
+        }
+        self.coverage_material.prepare(&self.gl, projection);
+        let start = cmd.idx_offset + 6;
+        let count = (cmd.num_of_elems - 2) * 3;
+        self.gl.draw_elements(
+            glow::TRIANGLES,
+            count as i32,
+            self.index_format.ty,
+            self.index_format.offset(start),
+        );
+        if !batches.is_empty() {
+            self.draw_instances(&self.glyph_material, projection, batches);
+        }
+        self.gl.stencil_mask(0xff);
+        self.gl.color_mask(true, true, true, true);
+    }
+
+    // Groups left open in `draw_list` end with the frame.
+    pub fn draw(&mut self, draw_context: &DrawContext, draw_list: &mut DrawList) -> Result<()> {
+        self.check_alive()?;
+        draw_list.end_all_groups();
+        let (width, height) = viewport_size(draw_context);
+        let stencil = draw_list
+            .cmds
+            .iter()
+            .any(|cmd| cmd.clip_op.is_some() || (cmd.is_text && cmd.num_of_insts > 0));
+        self.update_scene_target(width, height, stencil)?;
+        let scene_fbo = self.scene_target.as_ref().map(|target| target.fbo);
+        self.render(draw_context, draw_list, scene_fbo, false)?;
+        unsafe {
+            self.present_scene_target()?;
+            self.gl.flush();
+        }
+        self.frame_buffer_stats.capacity_bytes = self.vbo.capacity()
+            + self.ebo.capacity()
+            + self.inst_vbo.capacity()
+            + self.sdf_vbo.capacity();
+        self.buffer_stats = std::mem::take(&mut self.frame_buffer_stats);
+        Ok(())
+    }
+
+    // Draws the scene target over the default framebuffer with a textured quad, since blitting
+    // into the canvas fails when it is antialiased.
+    unsafe fn present_scene_target(&mut self) -> Result<()> {
+        let (width, height) = match &self.scene_target {
+            Some(target) => (target.width, target.height),
+            None => return Ok(()),
+        };
+        let resolved = self.acquire_offscreen_target(width, height)?;
+        if let Some(target) = &self.scene_target {
+            target.blit(&self.gl, resolved.fbo);
+        }
+        self.gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        self.gl.blend_func(glow::ONE, glow::ZERO);
+        self.gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.quad_vbo));
+        self.prepare_image(&IDENTITY, Vector2::zeros(), Vector2::repeat(1.));
+        self.gl
+            .bind_texture(glow::TEXTURE_2D, Some(resolved.texture));
+        self.gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+        self.gl
+            .bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo.buffer()));
+        self.release_offscreen_target(resolved);
+        Ok(())
+    }
+
+    // Renders into `fbo`, sized `screen_size * pixel_ratio`. `flip_y` puts the top of the scene
+    // in the first rows of the framebuffer, as in uploaded textures.
+    fn render(
+        &mut self,
+        draw_context: &DrawContext,
+        draw_list: &DrawList,
+        fbo: Option<WebFramebufferKey>,
+        flip_y: bool,
+    ) -> Result<()> {
         let w = draw_context.screen_size.x as f32;
         let h = draw_context.screen_size.y as f32;
         let scale = draw_context.scale;
@@ -90,31 +1478,40 @@ impl GlowBackend {
         let npx = 2. * translate.x / w + 1. / w;
         let npy = -2. * translate.y / h + 1. / h;
         #[rustfmt::skip]
-        let projection = [
+        let mut projection = [
             sx, 0., 0., 0.,
             0., -sy, 0., 0.,
             0., 0., -1., 0.,
             npx - 1., npy + 1., 0., 1.,
         ];
+        let mut clip_to_world = clip_to_world(draw_context);
+        if flip_y {
+            projection[5] = -projection[5];
+            projection[13] = -projection[13];
+            clip_to_world.column_mut(1).neg_mut();
+        }
         let vertices = draw_list.vertices();
         let indices = draw_list.indices();
+        self.index_format = IndexFormat::for_vertex_count(draw_list.vtx_buffer.len());
+        let batches = self.upload_instances(draw_list);
+        self.upload_sdf_shapes(draw_list);
+        let pixel_size = 1. / (scale * draw_context.pixel_ratio);
+        if draw_list.cmds.iter().any(|cmd| cmd.is_text) {
+            let (width, height) = viewport_size(draw_context);
+            self.fit_text_texture(width, height);
+        }
         unsafe {
-            self.gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
-            self.gl.buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                bytemuck::cast_slice(vertices),
-                glow::STREAM_DRAW,
-            );
-            self.gl
-                .bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(self.ebo));
-            self.gl.buffer_data_u8_slice(
-                glow::ELEMENT_ARRAY_BUFFER,
-                bytemuck::cast_slice(indices),
-                glow::STREAM_DRAW,
+            self.ebo.upload(
+                &self.gl,
+                &self.index_format.bytes(indices),
+                &mut self.frame_buffer_stats,
             );
+            self.vbo
+                .upload(&self.gl, vertices, &mut self.frame_buffer_stats);
             self.default_material.prepare(&self.gl, &projection);
-            let width = ((draw_context.screen_size.x as f32) * draw_context.pixel_ratio) as i32;
-            let height = ((draw_context.screen_size.y as f32) * draw_context.pixel_ratio) as i32;
+            let (width, height) = viewport_size(draw_context);
+            self.gl.bind_framebuffer(glow::FRAMEBUFFER, fbo);
+            let mut groups: Vec<GroupTarget> = vec![];
             self.gl.viewport(0, 0, width, height);
             self.gl.clear_color(
                 draw_context.bg_color.x,
@@ -123,150 +1520,555 @@ impl GlowBackend {
                 draw_context.bg_color.w,
             );
             self.gl
-                .clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
-            for cmd in &draw_list.cmds {
-                if cmd.is_text {
-                    self.gl.bind_framebuffer(glow::FRAMEBUFFER, self.fbo.into());
-                    self.gl.tex_image_2d(
-                        glow::TEXTURE_2D,
-                        0,
-                        glow::RGBA as i32,
-                        width,
-                        height,
-                        0,
-                        glow::RGBA,
-                        glow::UNSIGNED_BYTE,
-                        None,
+                .clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT | glow::STENCIL_BUFFER_BIT);
+            for (cmd, batches) in draw_list.cmds.iter().zip(&batches) {
+                let gradient = cmd.gradient.map(|i| &draw_list.gradients[i]);
+                self.apply_clip_rect(draw_context, cmd, height, flip_y);
+                self.apply_clip_depth(cmd.clip_depth);
+                self.apply_blend_mode(cmd.blend_mode);
+                let target_fbo = groups.last().map(GroupTarget::fbo).or(fbo);
+                match cmd.group_op {
+                    Some(GroupOp::Begin) => {
+                        let target = self.acquire_offscreen_target(width, height)?;
+                        let msaa = if self.msaa_samples > 0 {
+                            Some(self.acquire_msaa_group_target(width, height)?)
+                        } else {
+                            None
+                        };
+                        let group = GroupTarget { target, msaa };
+                        self.gl
+                            .bind_framebuffer(glow::FRAMEBUFFER, Some(group.fbo()));
+                        self.gl.disable(glow::SCISSOR_TEST);
+                        self.gl.clear_color(0., 0., 0., 0.);
+                        self.gl
+                            .clear(glow::COLOR_BUFFER_BIT | glow::STENCIL_BUFFER_BIT);
+                        groups.push(group);
+                        continue;
+                    }
+                    Some(GroupOp::End) => {
+                        if let Some(group) = groups.pop() {
+                            let parent_fbo = groups.last().map(GroupTarget::fbo).or(fbo);
+                            let target = &group.target;
+                            if let Some(msaa) = &group.msaa {
+                                msaa.blit(&self.gl, target.fbo);
+                            }
+                            if let Some(shadow) = &cmd.shadow {
+                                let px = draw_context.scale * draw_context.pixel_ratio;
+                                self.draw_shadow(shadow, px, target, cmd, parent_fbo, flip_y)?;
+                            }
+                            // resolving and blurring cover the whole target
+                            self.apply_clip_rect(draw_context, cmd, height, flip_y);
+                            self.gl.bind_framebuffer(glow::FRAMEBUFFER, parent_fbo);
+                            self.prepare_image(&IDENTITY, Vector2::zeros(), Vector2::repeat(1.));
+                            self.gl.bind_texture(glow::TEXTURE_2D, Some(target.texture));
+                            self.gl.draw_elements(
+                                glow::TRIANGLES,
+                                6,
+                                self.index_format.ty,
+                                self.index_format.offset(cmd.idx_offset),
+                            );
+                            self.default_material.prepare(&self.gl, &projection);
+                            self.release_group_target(group);
+                        }
+                        continue;
+                    }
+                    None => {}
+                }
+                if let Some(op) = cmd.clip_op {
+                    self.gl.enable(glow::STENCIL_TEST);
+                    match op {
+                        ClipOp::Push => {
+                            self.draw_stencil_coverage(&projection, cmd, batches);
+                            // raise the level where the path covers the current clip
+                            self.gl.color_mask(false, false, false, false);
+                            self.gl.stencil_func(
+                                glow::NOTEQUAL,
+                                clip_level(cmd.clip_depth + 1),
+                                WINDING_MASK,
+                            );
+                        }
+                        ClipOp::Pop => {
+                            self.gl.color_mask(false, false, false, false);
+                            self.gl.stencil_func(
+                                glow::LESS,
+                                clip_level(cmd.clip_depth - 1),
+                                CLIP_MASK,
+                            );
+                        }
+                    }
+                    self.gl.stencil_op(glow::KEEP, glow::KEEP, glow::REPLACE);
+                    self.default_material.prepare(&self.gl, &IDENTITY);
+                    self.gl.draw_elements(
+                        glow::TRIANGLES,
+                        6,
+                        self.index_format.ty,
+                        self.index_format.offset(cmd.idx_offset),
                     );
+                    self.gl.color_mask(true, true, true, true);
+                    self.default_material.prepare(&self.gl, &projection);
+                    continue;
+                }
+                // instance colors need the stencil to cover each pixel once
+                let instance_colors = gradient.is_none() && !batches.is_empty();
+                if cmd.is_text && (self.stencil_text || instance_colors) {
+                    self.draw_stencil_coverage(&projection, cmd, batches);
+                    // cover, clearing the winding for the next layer
+                    self.gl
+                        .stencil_func(glow::NOTEQUAL, clip_level(cmd.clip_depth), WINDING_MASK);
+                    self.gl.stencil_op(glow::KEEP, glow::KEEP, glow::REPLACE);
+                    if instance_colors {
+                        self.draw_instances(&self.glyph_cover_material, &projection, batches);
+                    }
+                    // the layer color covers what is left, the plain coverage past the quad
+                    if !instance_colors || cmd.num_of_elems > 2 {
+                        match gradient {
+                            Some(gradient) => self.prepare_gradient(
+                                &self.gradient_material,
+                                &IDENTITY,
+                                gradient,
+                                &clip_to_world,
+                            ),
+                            None => self.default_material.prepare(&self.gl, &IDENTITY),
+                        }
+                        self.gl.draw_elements(
+                            glow::TRIANGLES,
+                            6,
+                            self.index_format.ty,
+                            self.index_format.offset(cmd.idx_offset),
+                        );
+                    }
+                    self.apply_clip_depth(cmd.clip_depth);
+
+                    // reset
+                    self.default_material.prepare(&self.gl, &projection);
+                } else if cmd.is_text {
+                    self.gl.bind_framebuffer(glow::FRAMEBUFFER, self.fbo.into());
+                    self.gl
+                        .bind_texture(glow::TEXTURE_2D, Some(self.text_texture));
+                    self.gl.clear_color(0., 0., 0., 0.);
+                    self.gl.clear(glow::COLOR_BUFFER_BIT);
                     self.gl.blend_func(glow::SRC_ALPHA, glow::DST_ALPHA);
+                    self.coverage_material.prepare(&self.gl, &projection);
                     let start = cmd.idx_offset + 6;
                     let count = (cmd.num_of_elems - 2) * 3;
                     self.gl.draw_elements(
                         glow::TRIANGLES,
                         count as i32,
-                        glow::UNSIGNED_INT,
-                        (start * std::mem::size_of::<u32>()) as i32,
+                        self.index_format.ty,
+                        self.index_format.offset(start),
+                    );
+                    if !batches.is_empty() {
+                        self.draw_instances(&self.glyph_material, &projection, batches);
+                    }
+                    let text_material = match gradient {
+                        Some(gradient) => {
+                            self.prepare_gradient(
+                                &self.gradient_text_material,
+                                &IDENTITY,
+                                gradient,
+                                &clip_to_world,
+                            );
+                            &self.gradient_text_material
+                        }
+                        None => {
+                            self.text_material.prepare(&self.gl, &IDENTITY);
+                            &self.text_material
+                        }
+                    };
+                    self.gl.uniform_1_i32(
+                        text_material.uniform("fill_rule"),
+                        (cmd.fill_rule == FillRule::NonZero) as i32,
                     );
-                    #[rustfmt::skip]
-                    self.text_material.prepare(&self.gl, &[
-                        1., 0., 0., 0.,
-                        0., 1., 0., 0.,
-                        0., 0., 1., 0.,
-                        0., 0., 0., 1.,
-                    ]);
-                    self.gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+                    self.gl.bind_framebuffer(glow::FRAMEBUFFER, target_fbo);
                     // draw texture
-                    self.gl
-                        .blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+                    self.apply_blend_mode(cmd.blend_mode);
                     let start = cmd.idx_offset;
                     let count = 6;
                     self.gl.draw_elements(
                         glow::TRIANGLES,
                         count as i32,
-                        glow::UNSIGNED_INT,
-                        (start * std::mem::size_of::<u32>()) as i32,
+                        self.index_format.ty,
+                        self.index_format.offset(start),
                     );
 
                     // reset
                     self.default_material.prepare(&self.gl, &projection);
                 } else {
+                    let texture = match cmd.texture.map(|id| self.textures.get(&id)) {
+                        Some(Some(texture)) => Some(texture.texture),
+                        // layers of deleted textures are skipped
+                        Some(None) => continue,
+                        None => None,
+                    };
+                    if let Some(texture) = texture {
+                        self.prepare_image(&projection, cmd.uv_offset, cmd.uv_scale);
+                        self.gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                    } else if let Some(gradient) = gradient {
+                        self.prepare_gradient(
+                            &self.gradient_material,
+                            &projection,
+                            gradient,
+                            &Matrix3::identity(),
+                        );
+                    }
                     let start = cmd.idx_offset;
                     let count = cmd.num_of_elems * 3;
                     self.gl.draw_elements(
                         glow::TRIANGLES,
                         count as i32,
-                        glow::UNSIGNED_INT,
-                        (start * std::mem::size_of::<u32>()) as i32,
+                        self.index_format.ty,
+                        self.index_format.offset(start),
                     );
+                    if gradient.is_some() || texture.is_some() {
+                        self.default_material.prepare(&self.gl, &projection);
+                    }
+                }
+                if cmd.num_of_sdfs > 0 {
+                    self.draw_sdf_shapes(&projection, pixel_size, cmd);
                 }
             }
-            self.gl.flush();
+            self.gl.disable(glow::SCISSOR_TEST);
+            self.gl.disable(glow::STENCIL_TEST);
+            self.gl.bind_framebuffer(glow::FRAMEBUFFER, fbo);
         }
         Ok(())
     }
 }
 
+fn check_pixels_len(width: u32, height: u32, pixels: &[u8]) -> Result<()> {
+    let expected = width as usize * height as usize * 4;
+    if pixels.len() != expected {
+        return Err(anyhow::anyhow!(
+            "Expected {} bytes of RGBA8 pixels, got {}",
+            expected,
+            pixels.len()
+        ));
+    }
+    Ok(())
+}
+
+// Shaders output premultiplied colors whatever the mode.
+fn blend_factors(blend_mode: BlendMode) -> (u32, u32) {
+    match blend_mode {
+        BlendMode::Normal | BlendMode::PremultipliedNormal => {
+            (glow::ONE, glow::ONE_MINUS_SRC_ALPHA)
+        }
+        BlendMode::Additive | BlendMode::PremultipliedAdditive => (glow::ONE, glow::ONE),
+        BlendMode::Multiply | BlendMode::PremultipliedMultiply => {
+            (glow::DST_COLOR, glow::ONE_MINUS_SRC_ALPHA)
+        }
+        BlendMode::Screen => (glow::ONE, glow::ONE_MINUS_SRC_COLOR),
+    }
+}
+
+fn filter_param(filter: TextureFilter) -> u32 {
+    match filter {
+        TextureFilter::Nearest => glow::NEAREST,
+        TextureFilter::Linear => glow::LINEAR,
+    }
+}
+
+fn min_filter_param(filter: TextureFilter, mipmaps: bool) -> u32 {
+    match (filter, mipmaps) {
+        (TextureFilter::Nearest, true) => glow::NEAREST_MIPMAP_NEAREST,
+        (TextureFilter::Linear, true) => glow::LINEAR_MIPMAP_LINEAR,
+        (filter, false) => filter_param(filter),
+    }
+}
+
+fn wrap_param(wrap: TextureWrap) -> u32 {
+    match wrap {
+        TextureWrap::Clamp => glow::CLAMP_TO_EDGE,
+        TextureWrap::Repeat => glow::REPEAT,
+        TextureWrap::MirroredRepeat => glow::MIRRORED_REPEAT,
+    }
+}
+
+// Maps clip space to world space, for full-screen quads that still need world positions.
+fn clip_to_world(ctx: &DrawContext) -> Matrix3<f32> {
+    let half_width = ctx.screen_size.x as f32 * 0.5;
+    let half_height = ctx.screen_size.y as f32 * 0.5;
+    #[rustfmt::skip]
+    let m = Matrix3::new(
+        half_width, 0., half_width - ctx.translate.x,
+        0., -half_height, half_height - ctx.translate.y,
+        0., 0., ctx.scale,
+    );
+    m.unscale(ctx.scale)
+}
+
+fn viewport_size(ctx: &DrawContext) -> (i32, i32) {
+    let width = ((ctx.screen_size.x as f32) * ctx.pixel_ratio) as i32;
+    let height = ((ctx.screen_size.y as f32) * ctx.pixel_ratio) as i32;
+    (width, height)
+}
+
+// Device-pixel scissor box covering the world-space rect, with the origin at the bottom left
+// unless `flip_y`.
+fn scissor_rect(
+    ctx: &DrawContext,
+    min: Vector2<f32>,
+    max: Vector2<f32>,
+    height: i32,
+    flip_y: bool,
+) -> [i32; 4] {
+    let to_device = |p: Vector2<f32>| (p * ctx.scale + ctx.translate) * ctx.pixel_ratio;
+    let (min, max) = (to_device(min), to_device(max));
+    let left = min.x.floor() as i32;
+    let right = max.x.ceil() as i32;
+    let top = min.y.floor() as i32;
+    let bottom = max.y.ceil() as i32;
+    let y = if flip_y { top } else { height - bottom };
+    [left, y, (right - left).max(0), (bottom - top).max(0)]
+}
+
+// The stencil holds the clip level of each pixel in its high bits and fill winding in the rest.
+fn clip_level(depth: usize) -> i32 {
+    (depth << 4) as i32
+}
+
+const CLIP_MASK: u32 = 0xf0;
+const WINDING_MASK: u32 = 0x0f;
+
+// Taps on each side of the center of a blur pass, as in the shader.
+const BLUR_TAPS: f32 = 16.;
+
+// Enough buffers that the GPU is done with one before the ring comes back to it.
+const STREAM_BUFFER_RING: usize = 3;
+const MIN_STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+#[rustfmt::skip]
+const IDENTITY: [f32; 16] = [
+    1., 0., 0., 0.,
+    0., 1., 0., 0.,
+    0., 0., 1., 0.,
+    0., 0., 0., 1.,
+];
+
+// Byte offsets of the fields of `Vert`.
+const VERTEX_UV_OFFSET: i32 = 8;
+const VERTEX_COLOR_OFFSET: i32 = 12;
+const INSTANCE_FLOATS: usize = 10;
+// enough for a few nested groups with shadows at a couple of render sizes
+const MAX_POOLED_TARGETS: usize = 8;
+const GLYPH_INSTANCE_ATTRIBUTES: &[(&str, i32)] = &[
+    ("inst_x_axis", 2),
+    ("inst_y_axis", 2),
+    ("inst_offset", 2),
+    ("inst_color", 4),
+];
+const SDF_FLOATS: usize = 15;
+
 struct Material {
     program: WebProgramKey,
     projection_location: UniformLocation,
     position_location: u32,
+    // Not every shader reads the curve or texture coordinates.
+    uv_location: Option<u32>,
     color_location: u32,
+    uniforms: HashMap<String, UniformLocation>,
+}
+
+// Compiles a shader, deleting it again if it doesn't compile.
+unsafe fn compile_shader(gl: &Context, kind: u32, source: &str) -> Result<WebShaderKey> {
+    let shader = gl.create_shader(kind).map_err(glow_error)?;
+    gl.shader_source(shader, source);
+    gl.compile_shader(shader);
+    if !gl.get_shader_compile_status(shader) {
+        let log = gl.get_shader_info_log(shader);
+        gl.delete_shader(shader);
+        return Err(anyhow::anyhow!("Glow Error: {}", log));
+    }
+    Ok(shader)
 }
 
 impl Material {
     fn new(gl: &Context, vertex_shader_source: &str, fragment_shader_source: &str) -> Result<Self> {
         unsafe {
             let program = gl.create_program().map_err(glow_error)?;
-            let vertex_shader = gl.create_shader(glow::VERTEX_SHADER).map_err(glow_error)?;
-            gl.shader_source(vertex_shader, vertex_shader_source);
-            gl.compile_shader(vertex_shader);
-            if !gl.get_shader_compile_status(vertex_shader) {
-                return Err(anyhow::anyhow!(
-                    "Glow Error: {}",
-                    gl.get_shader_info_log(vertex_shader)
-                ));
-            }
-            gl.attach_shader(program, vertex_shader);
-            let fragment_shader = gl
-                .create_shader(glow::FRAGMENT_SHADER)
-                .map_err(glow_error)?;
-            gl.shader_source(fragment_shader, fragment_shader_source);
-            gl.compile_shader(fragment_shader);
-            if !gl.get_shader_compile_status(fragment_shader) {
-                return Err(anyhow::anyhow!(
-                    "Glow Error: {}",
-                    gl.get_shader_info_log(fragment_shader)
-                ));
+            track("program", 1);
+            let material = Self::link(gl, program, vertex_shader_source, fragment_shader_source);
+            if material.is_err() {
+                gl.delete_program(program);
+                track("program", -1);
             }
-            gl.attach_shader(program, fragment_shader);
-            gl.link_program(program);
-            if !gl.get_program_link_status(program) {
-                return Err(anyhow::anyhow!(
-                    "Glow Error: {}",
-                    gl.get_program_info_log(program)
-                ));
-            }
-            gl.use_program(Some(program));
-            gl.detach_shader(program, vertex_shader);
-            gl.delete_shader(vertex_shader);
-            gl.detach_shader(program, fragment_shader);
-            gl.delete_shader(fragment_shader);
-
-            let projection_location = gl
-                .get_uniform_location(program, "projection")
-                .ok_or_else(|| anyhow::anyhow!("No projection uniform variable"))?;
-            let position_location = gl
-                .get_attrib_location(program, "vert_position")
-                .ok_or_else(|| anyhow::anyhow!("No vert_position attribute"))?;
-            let color_location = gl
-                .get_attrib_location(program, "vert_color")
-                .ok_or_else(|| anyhow::anyhow!("No vert_color attribute"))?;
+            material
+        }
+    }
 
-            Ok(Self {
-                program,
-                projection_location,
-                position_location,
-                color_location,
-            })
+    unsafe fn link(
+        gl: &Context,
+        program: WebProgramKey,
+        vertex_shader_source: &str,
+        fragment_shader_source: &str,
+    ) -> Result<Self> {
+        let vertex_shader = compile_shader(gl, glow::VERTEX_SHADER, vertex_shader_source)?;
+        let fragment_shader =
+            match compile_shader(gl, glow::FRAGMENT_SHADER, fragment_shader_source) {
+                Ok(shader) => shader,
+                Err(err) => {
+                    gl.delete_shader(vertex_shader);
+                    return Err(err);
+                }
+            };
+        gl.attach_shader(program, vertex_shader);
+        gl.attach_shader(program, fragment_shader);
+        gl.link_program(program);
+        gl.detach_shader(program, vertex_shader);
+        gl.delete_shader(vertex_shader);
+        gl.detach_shader(program, fragment_shader);
+        gl.delete_shader(fragment_shader);
+        if !gl.get_program_link_status(program) {
+            return Err(anyhow::anyhow!(
+                "Glow Error: {}",
+                gl.get_program_info_log(program)
+            ));
         }
+        gl.use_program(Some(program));
+
+        let projection_location = gl
+            .get_uniform_location(program, "projection")
+            .ok_or_else(|| anyhow::anyhow!("No projection uniform variable"))?;
+        let position_location = gl
+            .get_attrib_location(program, "vert_position")
+            .ok_or_else(|| anyhow::anyhow!("No vert_position attribute"))?;
+        let uv_location = gl.get_attrib_location(program, "vert_uv");
+        let color_location = gl
+            .get_attrib_location(program, "vert_color")
+            .ok_or_else(|| anyhow::anyhow!("No vert_color attribute"))?;
+        let uniforms = (0..gl.get_active_uniforms(program))
+            .filter_map(|index| {
+                let uniform = gl.get_active_uniform(program, index)?;
+                let name = uniform.name.trim_end_matches("[0]").to_string();
+                let location = gl.get_uniform_location(program, &name)?;
+                Some((name, location))
+            })
+            .collect();
+
+        Ok(Self {
+            program,
+            projection_location,
+            position_location,
+            uv_location,
+            color_location,
+            uniforms,
+        })
     }
 
     fn prepare(&self, gl: &Context, projection: &[f32; 16]) {
         unsafe {
             gl.use_program(self.program.into());
-            let stride = (8 * std::mem::size_of::<f32>()) as i32;
+            let stride = std::mem::size_of::<Vert>() as i32;
             gl.enable_vertex_attrib_array(self.position_location);
-            gl.vertex_attrib_pointer_f32(self.position_location, 4, glow::FLOAT, false, stride, 0);
+            gl.vertex_attrib_pointer_f32(self.position_location, 2, glow::FLOAT, false, stride, 0);
+            if let Some(uv_location) = self.uv_location {
+                gl.enable_vertex_attrib_array(uv_location);
+                gl.vertex_attrib_pointer_f32(
+                    uv_location,
+                    2,
+                    glow::UNSIGNED_SHORT,
+                    true,
+                    stride,
+                    VERTEX_UV_OFFSET,
+                );
+            }
             gl.enable_vertex_attrib_array(self.color_location);
             gl.vertex_attrib_pointer_f32(
                 self.color_location,
                 4,
-                glow::FLOAT,
-                false,
+                glow::UNSIGNED_BYTE,
+                true,
                 stride,
-                (4 * std::mem::size_of::<f32>()) as i32,
+                VERTEX_COLOR_OFFSET,
             );
             gl.uniform_matrix_4_f32_slice(Some(&self.projection_location), false, projection);
         }
     }
+
+    fn uniform(&self, name: &str) -> Option<&UniformLocation> {
+        self.uniforms.get(name)
+    }
+
+    fn delete(&self, gl: &Context) {
+        unsafe { gl.delete_program(self.program) };
+        track("program", -1);
+    }
+
+    fn attrib_location(&self, gl: &Context, name: &str) -> Result<u32> {
+        unsafe {
+            gl.get_attrib_location(self.program, name)
+                .ok_or_else(|| anyhow::anyhow!("No {} attribute", name))
+        }
+    }
+}
+
+struct InstancedMaterial {
+    material: Material,
+    // location, number of floats and offset in floats of each per-instance attribute the shader
+    // reads; the others still take their place in the stride
+    attributes: Vec<(u32, i32, usize)>,
+    stride: usize,
+}
+
+impl InstancedMaterial {
+    fn new(
+        gl: &Context,
+        vertex_shader_source: &str,
+        fragment_shader_source: &str,
+        attributes: &[(&str, i32)],
+    ) -> Result<Self> {
+        let material = Material::new(gl, vertex_shader_source, fragment_shader_source)?;
+        let mut stride = 0;
+        let attributes = attributes
+            .iter()
+            .filter_map(|&(name, size)| {
+                let offset = stride;
+                stride += size as usize;
+                let location = material.attrib_location(gl, name).ok()?;
+                Some((location, size, offset))
+            })
+            .collect();
+        Ok(Self {
+            material,
+            attributes,
+            stride,
+        })
+    }
+
+    fn prepare(&self, gl: &Context, projection: &[f32; 16]) {
+        self.material.prepare(gl, projection);
+    }
+
+    fn uniform(&self, name: &str) -> Option<&UniformLocation> {
+        self.material.uniform(name)
+    }
+
+    fn prepare_instances(&self, gl: &Context, inst_offset: usize) {
+        unsafe {
+            let float_size = std::mem::size_of::<f32>();
+            let stride = (self.stride * float_size) as i32;
+            let base = (inst_offset * self.stride * float_size) as i32;
+            for &(location, size, offset) in &self.attributes {
+                gl.enable_vertex_attrib_array(location);
+                gl.vertex_attrib_pointer_f32(
+                    location,
+                    size,
+                    glow::FLOAT,
+                    false,
+                    stride,
+                    base + (offset * float_size) as i32,
+                );
+                gl.vertex_attrib_divisor(location, 1);
+            }
+        }
+    }
+
+    fn finish_instances(&self, gl: &Context) {
+        unsafe {
+            for &(location, _, _) in &self.attributes {
+                gl.vertex_attrib_divisor(location, 0);
+                gl.disable_vertex_attrib_array(location);
+            }
+        }
+    }
 }
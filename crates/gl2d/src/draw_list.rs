@@ -1,7 +1,79 @@
 use nalgebra::base::{Vector2, Vector4};
+use nalgebra::{Affine2, Point2};
+
+use crate::glyph::GlyphInstance;
+use crate::gradient::Gradient;
+use crate::group::{Group, GroupOp};
+use crate::sdf::SdfShape;
+use crate::shadow::Shadow;
+use crate::shape::CircleCache;
+use crate::texture::TextureId;
 
 pub type Color = Vector4<f32>;
 
+// Clip paths nested deeper than this are ignored, their levels sharing the stencil with fill
+// coverage.
+pub(crate) const MAX_CLIP_DEPTH: usize = 15;
+
+// Maximum distance, in device pixels, between a curve and the segments approximating it.
+pub(crate) const TOLERANCE_PX: f32 = 0.25;
+
+// Tolerance, in their own units, of curves built without a `DrawContext` at hand.
+pub(crate) const DEFAULT_TOLERANCE: f32 = 0.01;
+
+// Number of segments keeping the chords of an arc of `radius` within `tolerance` of the arc.
+pub(crate) fn arc_segment_count(radius: f32, angle: f32, tolerance: f32) -> usize {
+    if radius <= tolerance {
+        return 1;
+    }
+    let step = 2. * (1. - tolerance / radius).acos();
+    (angle.abs() / step).ceil().max(1.) as usize
+}
+
+// Number of segments keeping a quadratic within `tolerance`.
+pub(crate) fn quad_segment_count(
+    p0: Vector2<f32>,
+    c: Vector2<f32>,
+    p: Vector2<f32>,
+    tolerance: f32,
+) -> usize {
+    let dd = (p0 - c * 2. + p).norm();
+    (dd / (4. * tolerance)).sqrt().ceil().max(1.) as usize
+}
+
+// Number of segments keeping a cubic within `tolerance`.
+pub(crate) fn cubic_segment_count(
+    p0: Vector2<f32>,
+    c1: Vector2<f32>,
+    c2: Vector2<f32>,
+    p: Vector2<f32>,
+    tolerance: f32,
+) -> usize {
+    let dd = (p0 - c1 * 2. + c2).norm().max((c1 - c2 * 2. + p).norm());
+    (dd * 3. / (4. * tolerance)).sqrt().ceil().max(1.) as usize
+}
+
+// Number of quadratics keeping an arc of `radius` within `tolerance`. A quadratic spanning an
+// angle of 2h strays about radius * h^4 / 8 from the arc, at its middle.
+pub(crate) fn arc_quad_count(radius: f32, angle: f32, tolerance: f32) -> usize {
+    let step = 2. * (8. * tolerance / radius).powf(0.25);
+    (angle.abs() / step).ceil().max(1.) as usize
+}
+
+// Number of quadratics keeping a cubic within `tolerance` when each of them joins the ends of
+// a piece with the tangents averaged: the error is at most sqrt(3) / 36 * |p - 3 c2 + 3 c1 - p0|
+// for one piece and falls with the cube of the number of pieces.
+pub(crate) fn cubic_quad_count(
+    p0: Vector2<f32>,
+    c1: Vector2<f32>,
+    c2: Vector2<f32>,
+    p: Vector2<f32>,
+    tolerance: f32,
+) -> usize {
+    let error = 3f32.sqrt() / 36. * (p - c2 * 3. + c1 * 3. - p0).norm();
+    (error / tolerance).cbrt().ceil().max(1.) as usize
+}
+
 pub struct DrawContext {
     pub screen_size: Vector2<u32>,
     pub pixel_ratio: f32,
@@ -20,6 +92,57 @@ impl DrawContext {
             bg_color: Color::new(1., 1., 1., 1.),
         }
     }
+
+    pub fn is_visible(&self, min: Vector2<f32>, max: Vector2<f32>) -> bool {
+        let screen_left_top = (-self.translate).unscale(self.scale);
+        let screen_right_bottom = (self.screen_size.cast() - self.translate).unscale(self.scale);
+        min.x <= screen_right_bottom.x
+            && max.x >= screen_left_top.x
+            && min.y <= screen_right_bottom.y
+            && max.y >= screen_left_top.y
+    }
+
+    // Flattening tolerance in world units for the current zoom.
+    pub fn tolerance(&self) -> f32 {
+        TOLERANCE_PX / (self.scale * self.pixel_ratio)
+    }
+
+    pub fn arc_segment_count(&self, radius: f32, angle: f32) -> usize {
+        arc_segment_count(radius, angle, self.tolerance())
+    }
+}
+
+pub fn transformed_bounds(
+    transform: &Affine2<f32>,
+    min: Vector2<f32>,
+    max: Vector2<f32>,
+) -> (Vector2<f32>, Vector2<f32>) {
+    let corners = [
+        Point2::new(min.x, min.y),
+        Point2::new(max.x, min.y),
+        Point2::new(min.x, max.y),
+        Point2::new(max.x, max.y),
+    ];
+    corners.iter().fold(
+        (
+            Vector2::repeat(f32::INFINITY),
+            Vector2::repeat(f32::NEG_INFINITY),
+        ),
+        |(min, max), corner| {
+            let p = (transform * corner).coords;
+            (min.inf(&p), max.sup(&p))
+        },
+    )
+}
+
+pub fn points_bounds(points: &[Vector2<f32>]) -> (Vector2<f32>, Vector2<f32>) {
+    points.iter().fold(
+        (
+            Vector2::repeat(f32::INFINITY),
+            Vector2::repeat(f32::NEG_INFINITY),
+        ),
+        |(min, max), p| (min.inf(p), max.sup(p)),
+    )
 }
 
 #[derive(Debug, Clone)]
@@ -27,6 +150,19 @@ pub struct DrawList {
     pub cmds: Vec<DrawCmd>,
     pub idx_buffer: Vec<u32>,
     pub vtx_buffer: Vec<Vert>,
+    pub inst_buffer: Vec<(usize, GlyphInstance)>,
+    pub sdf_buffer: Vec<SdfShape>,
+    pub gradients: Vec<Gradient>,
+    // world-space rects, each already intersected with the ones below it
+    pub clip_stack: Vec<(Vector2<f32>, Vector2<f32>)>,
+    pub clip_depth: usize,
+    // local-to-world transforms, each already composed with the ones below it
+    pub transform_stack: Vec<Affine2<f32>>,
+    pub blend_mode: BlendMode,
+    pub(crate) group_stack: Vec<Group>,
+    pub circle_cache: Option<CircleCache>,
+    // routes lines, circles and rectangles to `SdfShape`s instead of tessellating them
+    pub sdf_shapes: bool,
 }
 
 impl DrawList {
@@ -35,6 +171,16 @@ impl DrawList {
             cmds: vec![DrawCmd::default()],
             idx_buffer: vec![],
             vtx_buffer: vec![],
+            inst_buffer: vec![],
+            sdf_buffer: vec![],
+            gradients: vec![],
+            clip_stack: vec![],
+            clip_depth: 0,
+            transform_stack: vec![],
+            blend_mode: BlendMode::default(),
+            group_stack: vec![],
+            circle_cache: Some(CircleCache::default()),
+            sdf_shapes: false,
         }
     }
 
@@ -43,6 +189,14 @@ impl DrawList {
         self.cmds.push(DrawCmd::default());
         self.idx_buffer.clear();
         self.vtx_buffer.clear();
+        self.inst_buffer.clear();
+        self.sdf_buffer.clear();
+        self.gradients.clear();
+        self.clip_stack.clear();
+        self.clip_depth = 0;
+        self.transform_stack.clear();
+        self.blend_mode = BlendMode::default();
+        self.group_stack.clear();
     }
 
     pub fn new_layer(&mut self) {
@@ -50,35 +204,90 @@ impl DrawList {
             idx_offset: self.idx_buffer.len(),
             vtx_offset: self.vtx_buffer.len(),
             num_of_elems: 0,
+            inst_offset: self.inst_buffer.len(),
+            num_of_insts: 0,
+            sdf_offset: self.sdf_buffer.len(),
+            num_of_sdfs: 0,
             is_text: false,
+            fill_rule: FillRule::default(),
+            gradient: None,
+            texture: None,
+            uv_offset: Vector2::zeros(),
+            uv_scale: Vector2::repeat(1.),
+            clip_rect: self.clip_rect(),
+            clip_depth: self.layer_clip_depth(),
+            clip_op: None,
+            blend_mode: self.blend_mode,
+            group_op: None,
+            shadow: None,
         });
     }
 
+    // Opens a layer of the same kind as the current one for a state change in the middle of it;
+    // text layers start over with a quad of the same color. Clip path layers aren't continued.
+    pub(crate) fn reopen_layer(&mut self) {
+        let last = self.cmds.last().unwrap().clone();
+        if last.is_text && last.clip_op.is_none() {
+            let col = self.vtx_buffer[last.vtx_offset].col;
+            self.push_text_layer(col, last.fill_rule);
+        } else {
+            self.new_layer();
+            if last.clip_op.is_some() {
+                return;
+            }
+        }
+        let cmd = self.cmds.last_mut().unwrap();
+        cmd.gradient = last.gradient;
+        cmd.texture = last.texture;
+        cmd.uv_offset = last.uv_offset;
+        cmd.uv_scale = last.uv_scale;
+    }
+
     pub fn new_text_layer(&mut self, col: Color) {
+        self.new_text_layer_with_fill_rule(col, FillRule::EvenOdd);
+    }
+
+    pub fn new_text_layer_with_fill_rule(&mut self, col: Color, fill_rule: FillRule) {
+        let col = self.vertex_color(col);
+        self.push_text_layer(col, fill_rule);
+    }
+
+    // `col` is already premultiplied.
+    pub(crate) fn push_text_layer(&mut self, col: [u8; 4], fill_rule: FillRule) {
         self.cmds.push(DrawCmd {
             idx_offset: self.idx_buffer.len(),
             vtx_offset: self.vtx_buffer.len(),
             num_of_elems: 0,
+            inst_offset: self.inst_buffer.len(),
+            num_of_insts: 0,
+            sdf_offset: self.sdf_buffer.len(),
+            num_of_sdfs: 0,
             is_text: true,
+            fill_rule,
+            gradient: None,
+            texture: None,
+            uv_offset: Vector2::zeros(),
+            uv_scale: Vector2::repeat(1.),
+            clip_rect: self.clip_rect(),
+            clip_depth: self.layer_clip_depth(),
+            clip_op: None,
+            blend_mode: self.blend_mode,
+            group_op: None,
+            shadow: None,
         });
-        let a = self.push_vert(Vert {
-            pos: Vector4::new(-1., -1., 0., 1.),
-            col,
-        });
-        let b = self.push_vert(Vert {
-            pos: Vector4::new(1., -1., 0., 1.),
-            col,
-        });
-        let c = self.push_vert(Vert {
-            pos: Vector4::new(-1., 1., 0., 1.),
-            col,
-        });
-        let d = self.push_vert(Vert {
-            pos: Vector4::new(1., 1., 0., 1.),
-            col,
-        });
-        self.push_elem(a, b, c);
-        self.push_elem(b, c, d);
+        self.push_clip_space_quad(col);
+    }
+
+    // Covers the whole viewport, drawn with an identity projection, so it skips the transform.
+    fn push_clip_space_quad(&mut self, col: [u8; 4]) {
+        let idx = self.vtx_buffer.len() as u32;
+        for &(x, y) in [(-1., -1.), (1., -1.), (-1., 1.), (1., 1.)].iter() {
+            let mut vert = Vert::new(Vector4::new(x, y, 0., 1.), Color::zeros());
+            vert.col = col;
+            self.vtx_buffer.push(vert);
+        }
+        self.push_elem(idx, idx + 1, idx + 2);
+        self.push_elem(idx + 1, idx + 2, idx + 3);
     }
 
     pub fn reserve(&mut self, idx_count: usize, vtx_count: usize) {
@@ -86,18 +295,229 @@ impl DrawList {
         self.vtx_buffer.reserve(vtx_count);
     }
 
-    pub fn push_vert(&mut self, vert: Vert) -> u32 {
+    // Buffers hold premultiplied colors; those of straight blend modes get multiplied here.
+    pub fn push_vert(&mut self, mut vert: Vert) -> u32 {
+        if let Some(transform) = self.transform_stack.last() {
+            vert.pos = (transform * Point2::from(vert.pos)).coords;
+        }
+        if !self.blend_mode.is_premultiplied() {
+            vert.col = premultiply_color(vert.col);
+        }
         let idx = self.vtx_buffer.len() as u32;
         self.vtx_buffer.push(vert);
         idx
     }
 
+    // Layers draw their `SdfShape`s after their triangles and instances, so those added after
+    // shapes go to a new layer.
+    pub(crate) fn split_after_sdf_shapes(&mut self) {
+        if self.cmds.last().unwrap().num_of_sdfs > 0 {
+            self.reopen_layer();
+        }
+    }
+
     pub fn push_elem(&mut self, a: u32, b: u32, c: u32) {
+        self.split_after_sdf_shapes();
         self.idx_buffer.push(a);
         self.idx_buffer.push(b);
         self.idx_buffer.push(c);
         self.cmds.last_mut().unwrap().num_of_elems += 1;
     }
+
+    pub fn push_instance(&mut self, mesh_index: usize, instance: GlyphInstance) {
+        self.split_after_sdf_shapes();
+        self.inst_buffer.push((mesh_index, instance));
+        self.cmds.last_mut().unwrap().num_of_insts += 1;
+    }
+
+    // Layers with a gradient, texture or clip mask can't draw `SdfShape`s, so they are rejected
+    // there; the shape methods tessellate them instead.
+    pub fn push_sdf_shape(&mut self, shape: SdfShape) {
+        let (min, max) = shape.bounds();
+        if !self.layer_draws_sdf_shapes() || !self.is_inside_clip(min, max) {
+            return;
+        }
+        let mut shape = match self.transform_stack.last() {
+            Some(transform) => shape.transformed(transform),
+            None => shape,
+        };
+        if !self.blend_mode.is_premultiplied() {
+            let a = shape.col.w;
+            shape.col = Color::new(shape.col.x * a, shape.col.y * a, shape.col.z * a, a);
+        }
+        self.sdf_buffer.push(shape);
+        self.cmds.last_mut().unwrap().num_of_sdfs += 1;
+    }
+}
+
+impl DrawList {
+    // `col` packed as the current blend mode stores it.
+    pub(crate) fn vertex_color(&self, col: Color) -> [u8; 4] {
+        let col = pack_color(col);
+        if self.blend_mode.is_premultiplied() {
+            col
+        } else {
+            premultiply_color(col)
+        }
+    }
+
+    // Opens a new layer when the mode changes, so it applies to everything added afterwards.
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        if self.blend_mode != blend_mode {
+            self.blend_mode = blend_mode;
+            self.reopen_layer();
+        }
+    }
+
+    // Applies `transform` on top of the current one to everything added until `pop_transform`.
+    pub fn push_transform(&mut self, transform: &Affine2<f32>) {
+        let transform = self.transform() * transform;
+        self.transform_stack.push(transform);
+    }
+
+    pub fn pop_transform(&mut self) {
+        self.transform_stack.pop();
+    }
+
+    pub fn transform(&self) -> Affine2<f32> {
+        self.transform_stack
+            .last()
+            .copied()
+            .unwrap_or_else(Affine2::identity)
+    }
+
+    // Flattening tolerance in the units of the current transform, for the current zoom.
+    pub fn tolerance(&self, ctx: &DrawContext) -> f32 {
+        ctx.tolerance() / self.transform_scale()
+    }
+
+    // Largest factor the current transform stretches lengths by, for tessellating curves.
+    pub(crate) fn transform_scale(&self) -> f32 {
+        match self.transform_stack.last() {
+            Some(transform) => {
+                let m = transform.matrix();
+                let (a, b, c, d) = (m[(0, 0)], m[(1, 0)], m[(0, 1)], m[(1, 1)]);
+                let sum = a * a + b * b + c * c + d * d;
+                let det = a * d - b * c;
+                ((sum + (sum * sum - 4. * det * det).max(0.).sqrt()) * 0.5).sqrt()
+            }
+            None => 1.,
+        }
+    }
+
+    // `SdfShape`s only survive transforms that keep their proportions and plain layers; other
+    // shapes get tessellated instead.
+    pub(crate) fn layer_draws_sdf_shapes(&self) -> bool {
+        let cmd = self.cmds.last().unwrap();
+        cmd.gradient.is_none() && cmd.texture.is_none() && cmd.clip_op.is_none()
+    }
+
+    pub(crate) fn use_sdf_shapes(&self) -> bool {
+        if !self.sdf_shapes || !self.layer_draws_sdf_shapes() {
+            return false;
+        }
+        match self.transform_stack.last() {
+            Some(transform) => {
+                let m = transform.matrix();
+                let (x, y) = (m.fixed_slice::<2, 1>(0, 0), m.fixed_slice::<2, 1>(0, 1));
+                (x.norm() - y.norm()).abs() <= 1e-4 * x.norm()
+                    && x.dot(&y).abs() <= 1e-4 * x.norm_squared()
+            }
+            None => true,
+        }
+    }
+
+    // Clip rects are given in the coordinates of the current transform, and clip to their
+    // world-space bounds.
+    pub fn push_clip_rect(&mut self, min: Vector2<f32>, max: Vector2<f32>) {
+        let (min, max) = self.world_bounds(min, max);
+        let (min, max) = match self.clip_rect() {
+            Some((clip_min, clip_max)) => (min.sup(&clip_min), max.inf(&clip_max)),
+            None => (min, max),
+        };
+        self.clip_stack.push((min, max));
+        self.reopen_layer();
+    }
+
+    pub fn pop_clip_rect(&mut self) {
+        self.clip_stack.pop();
+        self.reopen_layer();
+    }
+
+    pub fn clip_rect(&self) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        self.clip_stack.last().copied()
+    }
+
+    fn world_bounds(&self, min: Vector2<f32>, max: Vector2<f32>) -> (Vector2<f32>, Vector2<f32>) {
+        match self.transform_stack.last() {
+            Some(transform) => transformed_bounds(transform, min, max),
+            None => (min, max),
+        }
+    }
+
+    // Bounds are in the coordinates of the current transform.
+    pub fn is_inside_clip(&self, min: Vector2<f32>, max: Vector2<f32>) -> bool {
+        let (min, max) = self.world_bounds(min, max);
+        self.is_inside_clip_rect(min, max)
+    }
+
+    fn is_inside_clip_rect(&self, min: Vector2<f32>, max: Vector2<f32>) -> bool {
+        match self.clip_rect() {
+            Some((clip_min, clip_max)) => {
+                min.x <= clip_max.x
+                    && max.x >= clip_min.x
+                    && min.y <= clip_max.y
+                    && max.y >= clip_min.y
+            }
+            None => true,
+        }
+    }
+
+    pub fn is_visible(&self, ctx: &DrawContext, min: Vector2<f32>, max: Vector2<f32>) -> bool {
+        let (min, max) = self.world_bounds(min, max);
+        ctx.is_visible(min, max) && self.is_inside_clip_rect(min, max)
+    }
+
+    // Opens a layer whose glyph coverage, under `fill_rule`, becomes the clip for everything
+    // drawn after the next `new_layer` until `pop_clip_path`. Clip paths nested deeper than
+    // `MAX_CLIP_DEPTH` don't clip; their coverage goes to a transparent text layer.
+    pub fn push_clip_layer(&mut self, fill_rule: FillRule) {
+        self.new_text_layer_with_fill_rule(Color::zeros(), fill_rule);
+        if self.group_clip_depth() < MAX_CLIP_DEPTH {
+            self.cmds.last_mut().unwrap().clip_op = Some(ClipOp::Push);
+        }
+        self.clip_depth += 1;
+    }
+
+    // Clip paths pushed outside of the current group stay until the group ends.
+    pub fn pop_clip_path(&mut self) {
+        let depth = self.group_clip_depth();
+        if depth == 0 {
+            return;
+        }
+        if depth > MAX_CLIP_DEPTH {
+            self.clip_depth -= 1;
+            self.new_layer();
+            return;
+        }
+        self.new_layer();
+        self.cmds.last_mut().unwrap().clip_op = Some(ClipOp::Pop);
+        self.push_clip_space_quad([0; 4]);
+        self.clip_depth -= 1;
+        self.new_layer();
+    }
+
+    // Groups render to targets with stencils of their own, so their layers count clip paths
+    // from the start of the innermost group.
+    fn group_clip_depth(&self) -> usize {
+        let base = self.group_stack.last().map_or(0, |group| group.clip_depth);
+        self.clip_depth - base
+    }
+
+    // Number of clip paths a layer added now is drawn inside of.
+    pub(crate) fn layer_clip_depth(&self) -> usize {
+        self.group_clip_depth().min(MAX_CLIP_DEPTH)
+    }
 }
 
 impl Default for DrawList {
@@ -107,14 +527,8 @@ impl Default for DrawList {
 }
 
 impl DrawList {
-    pub fn vertices(&self) -> &[f32] {
-        unsafe {
-            let len =
-                std::mem::size_of::<Vert>() / std::mem::size_of::<f32>() * self.vtx_buffer.len();
-            #[allow(clippy::transmute_ptr_to_ptr)]
-            let ptr = std::mem::transmute::<*const Vert, *const f32>(self.vtx_buffer.as_ptr());
-            &*std::ptr::slice_from_raw_parts(ptr, len)
-        }
+    pub fn vertices(&self) -> &[u8] {
+        vert_bytes(&self.vtx_buffer)
     }
 
     pub fn indices(&self) -> &[u32] {
@@ -122,11 +536,49 @@ impl DrawList {
     }
 }
 
-#[derive(Debug, Clone)]
+pub fn vert_bytes(vertices: &[Vert]) -> &[u8] {
+    unsafe {
+        let len = std::mem::size_of_val(vertices);
+        let ptr = vertices.as_ptr() as *const u8;
+        &*std::ptr::slice_from_raw_parts(ptr, len)
+    }
+}
+
+// 16 bytes: the position, curve or texture coordinates as normalized shorts, and an RGBA8 color.
+#[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct Vert {
-    pub pos: Vector4<f32>,
-    pub col: Color,
+    pub pos: Vector2<f32>,
+    pub uv: [u16; 2],
+    pub col: [u8; 4],
+}
+
+impl Vert {
+    // The coordinates in `pos.zw` are clamped to [0, 1].
+    pub fn new(pos: Vector4<f32>, col: Color) -> Self {
+        let unorm16 = |v: f32| (v.clamp(0., 1.) * 65535. + 0.5) as u16;
+        Self {
+            pos: pos.xy(),
+            uv: [unorm16(pos.z), unorm16(pos.w)],
+            col: pack_color(col),
+        }
+    }
+}
+
+pub(crate) fn pack_color(col: Color) -> [u8; 4] {
+    let unorm8 = |v: f32| (v.clamp(0., 1.) * 255. + 0.5) as u8;
+    [unorm8(col.x), unorm8(col.y), unorm8(col.z), unorm8(col.w)]
+}
+
+pub(crate) fn premultiply_color(col: [u8; 4]) -> [u8; 4] {
+    let a = col[3] as u32;
+    let premultiply = |c: u8| ((c as u32 * a + 127) / 255) as u8;
+    [
+        premultiply(col[0]),
+        premultiply(col[1]),
+        premultiply(col[2]),
+        col[3],
+    ]
 }
 
 #[derive(Debug, Clone, Default)]
@@ -134,5 +586,89 @@ pub struct DrawCmd {
     pub vtx_offset: usize,
     pub idx_offset: usize,
     pub num_of_elems: usize,
+    pub inst_offset: usize,
+    pub num_of_insts: usize,
+    pub sdf_offset: usize,
+    pub num_of_sdfs: usize,
     pub is_text: bool,
+    pub fill_rule: FillRule,
+    pub gradient: Option<usize>,
+    pub texture: Option<TextureId>,
+    // texture coordinates of texture layers are stored as `(uv - uv_offset) / uv_scale`, to go
+    // past [0, 1] without losing precision
+    pub uv_offset: Vector2<f32>,
+    pub uv_scale: Vector2<f32>,
+    pub clip_rect: Option<(Vector2<f32>, Vector2<f32>)>,
+    // number of clip paths the layer is drawn inside of
+    pub clip_depth: usize,
+    pub clip_op: Option<ClipOp>,
+    pub blend_mode: BlendMode,
+    pub group_op: Option<GroupOp>,
+    // only set on `GroupOp::End`
+    pub shadow: Option<Shadow>,
+}
+
+// Commands that only update the clip mask; `Push` layers hold the coverage of the new clip path
+// after their clip-space quad, `Pop` layers just the quad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipOp {
+    Push,
+    Pop,
+}
+
+impl DrawCmd {
+    pub fn is_empty(&self) -> bool {
+        self.num_of_elems == 0 && self.num_of_insts == 0 && self.num_of_sdfs == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule {
+    EvenOdd,
+    NonZero,
+}
+
+impl Default for FillRule {
+    fn default() -> Self {
+        FillRule::EvenOdd
+    }
+}
+
+// The premultiplied variants take colors already multiplied by their alpha, the others straight
+// ones. Screen takes straight colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    Additive,
+    Multiply,
+    Screen,
+    PremultipliedNormal,
+    PremultipliedAdditive,
+    PremultipliedMultiply,
+}
+
+impl BlendMode {
+    pub fn premultiplied(self) -> Self {
+        match self {
+            BlendMode::Normal => BlendMode::PremultipliedNormal,
+            BlendMode::Additive => BlendMode::PremultipliedAdditive,
+            BlendMode::Multiply => BlendMode::PremultipliedMultiply,
+            mode => mode,
+        }
+    }
+
+    pub fn is_premultiplied(self) -> bool {
+        matches!(
+            self,
+            BlendMode::PremultipliedNormal
+                | BlendMode::PremultipliedAdditive
+                | BlendMode::PremultipliedMultiply
+        )
+    }
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Normal
+    }
 }
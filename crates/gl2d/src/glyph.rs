@@ -1,23 +1,26 @@
-use nalgebra::{Vector2, Vector4};
-use owned_ttf_parser::{Face, GlyphId, OutlineBuilder, Rect};
+use std::cell::Cell;
+use std::collections::HashMap;
 
-use crate::draw_list::{Color, DrawContext, DrawList, Vert};
+use nalgebra::{Affine2, Point2, Vector2, Vector4};
+use owned_ttf_parser::{Face, GlyphId, OutlineBuilder};
+
+use crate::draw_list::{
+    cubic_quad_count, transformed_bounds, Color, DrawContext, DrawList, FillRule, Vert,
+};
 
 #[derive(Debug, Default)]
 pub struct Glyph {
     idx_buffer: Vec<u32>,
     vtx_buffer: Vec<Vert>,
     num_of_elems: usize,
-    bounding_box: Option<Rect>,
+    bounding_box: Option<(Vector2<f32>, Vector2<f32>)>,
 }
 
 impl Glyph {
     pub fn new(face: &Face, glyph_id: GlyphId) -> Glyph {
         let mut builder = GlyphBuilder::default();
-        let rect = face.outline_glyph(glyph_id, &mut builder);
-        let mut glyph = builder.build();
-        glyph.bounding_box = rect;
-        glyph
+        face.outline_glyph(glyph_id, &mut builder);
+        builder.build()
     }
 
     pub fn reserve(&mut self, idx_count: usize, vtx_count: usize) {
@@ -37,89 +40,284 @@ impl Glyph {
         self.idx_buffer.push(c);
         self.num_of_elems += 1;
     }
+
+    pub fn vertices(&self) -> &[Vert] {
+        &self.vtx_buffer
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.idx_buffer
+    }
+
+    pub fn num_of_elems(&self) -> usize {
+        self.num_of_elems
+    }
+
+    fn is_visible(
+        &self,
+        draw_list: &DrawList,
+        ctx: &DrawContext,
+        transform: &Affine2<f32>,
+    ) -> bool {
+        if let Some((min, max)) = self.bounding_box {
+            let (min, max) = transformed_bounds(transform, min, max);
+            draw_list.is_visible(ctx, min, max)
+        } else {
+            true
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GlyphCache {
+    glyphs: Vec<Glyph>,
+    indices: HashMap<GlyphId, usize>,
+    // glyphs the backend has meshes for, set by `GlowBackend::upload_glyphs`
+    uploaded: Cell<usize>,
+}
+
+impl GlyphCache {
+    pub fn get_or_insert(&mut self, face: &Face, glyph_id: GlyphId) -> usize {
+        let glyphs = &mut self.glyphs;
+        *self.indices.entry(glyph_id).or_insert_with(|| {
+            glyphs.push(Glyph::new(face, glyph_id));
+            glyphs.len() - 1
+        })
+    }
+
+    pub fn get(&self, index: usize) -> &Glyph {
+        &self.glyphs[index]
+    }
+
+    pub fn glyphs(&self) -> &[Glyph] {
+        &self.glyphs
+    }
+
+    pub(crate) fn set_uploaded(&self, count: usize) {
+        self.uploaded.set(count);
+    }
+
+    pub fn is_uploaded(&self, index: usize) -> bool {
+        index < self.uploaded.get()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GlyphInstance {
+    pub transform: Affine2<f32>,
+    // premultiplied
+    pub col: [u8; 4],
 }
 
 impl DrawList {
-    pub fn add_glyph(
-        &mut self,
-        ctx: &DrawContext,
-        position: Vector2<f32>,
-        scale: f32,
-        glyph: &Glyph,
-    ) {
-        if let Some(bb) = glyph.bounding_box {
-            let glyph_left_top = Vector2::new(bb.x_min as f32, -bb.y_max as f32) * scale + position;
-            let glyph_right_bottom =
-                Vector2::new(bb.x_max as f32, bb.y_min as f32) * scale + position;
-            let screen_left_top = (-ctx.translate).unscale(ctx.scale);
-            let screen_right_bottom = (ctx.screen_size.cast() - ctx.translate).unscale(ctx.scale);
-            let hit = glyph_left_top.x <= screen_right_bottom.x
-                && glyph_right_bottom.x >= screen_left_top.x
-                && glyph_left_top.y <= screen_right_bottom.y
-                && glyph_right_bottom.y >= screen_left_top.y;
-            if !hit {
-                return;
-            }
+    pub fn add_glyph(&mut self, ctx: &DrawContext, transform: &Affine2<f32>, glyph: &Glyph) {
+        if !glyph.is_visible(self, ctx, transform) {
+            return;
         }
+        self.split_after_sdf_shapes();
+        let transform = &(self.transform() * transform);
         let vtx_buffer_len = self.vtx_buffer.len() as u32;
         self.idx_buffer
             .extend(glyph.idx_buffer.iter().map(|idx| idx + vtx_buffer_len));
-        for &Vert { ref pos, col } in glyph.vtx_buffer.iter() {
-            let xy = pos.xy() * scale + position;
+        for vert in glyph.vtx_buffer.iter() {
+            let xy = transform * Point2::from(vert.pos);
             self.vtx_buffer.push(Vert {
-                pos: Vector4::new(xy.x, xy.y, pos.z, pos.w),
-                col,
+                pos: xy.coords,
+                ..*vert
             });
         }
         self.cmds.last_mut().unwrap().num_of_elems += glyph.num_of_elems;
     }
+
+    // Draws glyph `index` of `glyph_cache` in the current text layer, or a new one of `col`.
+    // Instances cover their coverage in their own color, except in gradient layers; a pixel
+    // inside the triangles of several glyphs takes the color of the first one. Glyphs cached
+    // after the last `upload_glyphs` are added as plain coverage instead. Shapes drawn afterwards
+    // need a `new_layer` first.
+    pub fn add_glyph_instance(
+        &mut self,
+        ctx: &DrawContext,
+        transform: &Affine2<f32>,
+        glyph_cache: &GlyphCache,
+        index: usize,
+        col: Color,
+    ) {
+        let glyph = glyph_cache.get(index);
+        if !glyph.is_visible(self, ctx, transform) {
+            return;
+        }
+        let col = self.vertex_color(col);
+        let cmd = self.cmds.last().unwrap();
+        if !glyph_cache.is_uploaded(index) {
+            // plain coverage takes the layer color
+            let same_color = cmd.is_text
+                && (cmd.clip_op.is_some()
+                    || cmd.gradient.is_some()
+                    || self.vtx_buffer[cmd.vtx_offset].col == col);
+            if !same_color {
+                let fill_rule = if cmd.is_text {
+                    cmd.fill_rule
+                } else {
+                    FillRule::default()
+                };
+                self.push_text_layer(col, fill_rule);
+            }
+            self.add_glyph(ctx, transform, glyph);
+            return;
+        }
+        if !cmd.is_text {
+            self.push_text_layer(col, FillRule::default());
+        }
+        self.push_instance(
+            index,
+            GlyphInstance {
+                transform: self.transform() * transform,
+                col,
+            },
+        );
+    }
 }
 
-#[derive(Default)]
+const COVERAGE_COLOR: Color = Color::new(0., 0., 1. / 255., 1.);
+// Font outlines are flattened once, in font units, a few thousand per em.
+const FONT_TOLERANCE: f32 = 1.;
+
 pub struct GlyphBuilder {
     first_point: Vector2<f32>,
     current_point: Vector2<f32>,
     contour_count: usize,
+    // maximum distance between the cubics and the quadratics replacing them
+    tolerance: f32,
     glyph: Glyph,
 }
 
+impl Default for GlyphBuilder {
+    fn default() -> Self {
+        Self::new(FONT_TOLERANCE)
+    }
+}
+
 impl GlyphBuilder {
+    pub fn new(tolerance: f32) -> Self {
+        Self {
+            first_point: Vector2::zeros(),
+            current_point: Vector2::zeros(),
+            contour_count: 0,
+            tolerance,
+            glyph: Glyph::default(),
+        }
+    }
+
     #[inline]
     pub fn add_triangle(&mut self, a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>, col: Color) {
         self.glyph.reserve(3, 3);
-        let a = self.glyph.push_vert(Vert {
-            pos: Vector4::new(a.x, a.y, 0., 1.),
-            col,
-        });
-        let b = self.glyph.push_vert(Vert {
-            pos: Vector4::new(b.x, b.y, 0., 1.),
-            col,
-        });
-        let c = self.glyph.push_vert(Vert {
-            pos: Vector4::new(c.x, c.y, 1., 1.),
-            col,
-        });
+        let a = self
+            .glyph
+            .push_vert(Vert::new(Vector4::new(a.x, a.y, 0., 1.), col));
+        let b = self
+            .glyph
+            .push_vert(Vert::new(Vector4::new(b.x, b.y, 0., 1.), col));
+        let c = self
+            .glyph
+            .push_vert(Vert::new(Vector4::new(c.x, c.y, 1., 1.), col));
         self.glyph.push_elem(a, b, c);
     }
 
     #[inline]
     pub fn add_curve(&mut self, a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>, col: Color) {
         self.glyph.reserve(3, 3);
-        let a = self.glyph.push_vert(Vert {
-            pos: Vector4::new(a.x, a.y, 0., 0.),
-            col,
-        });
-        let b = self.glyph.push_vert(Vert {
-            pos: Vector4::new(b.x, b.y, 0.5, 0.),
-            col,
-        });
-        let c = self.glyph.push_vert(Vert {
-            pos: Vector4::new(c.x, c.y, 1., 1.),
-            col,
-        });
+        let a = self
+            .glyph
+            .push_vert(Vert::new(Vector4::new(a.x, a.y, 0., 0.), col));
+        let b = self
+            .glyph
+            .push_vert(Vert::new(Vector4::new(b.x, b.y, 0.5, 0.), col));
+        let c = self
+            .glyph
+            .push_vert(Vert::new(Vector4::new(c.x, c.y, 1., 1.), col));
         self.glyph.push_elem(a, b, c);
     }
 
+    pub fn begin_contour(&mut self, p: Vector2<f32>) {
+        self.first_point = p;
+        self.current_point = p;
+        self.contour_count = 0;
+        self.extend_bounds(p);
+    }
+
+    pub fn add_line(&mut self, p: Vector2<f32>) {
+        self.contour_count += 1;
+        if self.contour_count >= 2 {
+            self.add_triangle(self.first_point, self.current_point, p, COVERAGE_COLOR);
+        }
+        self.current_point = p;
+        self.extend_bounds(p);
+    }
+
+    pub fn add_quad(&mut self, c: Vector2<f32>, p: Vector2<f32>) {
+        self.contour_count += 1;
+        if self.contour_count >= 2 {
+            self.add_triangle(self.first_point, self.current_point, p, COVERAGE_COLOR);
+        }
+        self.add_curve(self.current_point, c, p, COVERAGE_COLOR);
+        self.current_point = p;
+        self.extend_bounds(c);
+        self.extend_bounds(p);
+    }
+
+    // Approximates the cubic with quadratics, one per subdivided piece.
+    pub fn add_cubic(&mut self, c1: Vector2<f32>, c2: Vector2<f32>, p: Vector2<f32>) {
+        let p0 = self.current_point;
+        let point = |t: f32| {
+            let mt = 1. - t;
+            p0 * (mt * mt * mt) + c1 * (3. * mt * mt * t) + c2 * (3. * mt * t * t) + p * (t * t * t)
+        };
+        let derivative = |t: f32| {
+            let mt = 1. - t;
+            (c1 - p0) * (3. * mt * mt) + (c2 - c1) * (6. * mt * t) + (p - c2) * (3. * t * t)
+        };
+        let quad_count = cubic_quad_count(p0, c1, c2, p, self.tolerance);
+        for i in 0..quad_count {
+            let t0 = i as f32 / quad_count as f32;
+            let t1 = (i + 1) as f32 / quad_count as f32;
+            let dt = t1 - t0;
+            let (a, b) = (point(t0), point(t1));
+            let c = (a + b) * 0.5 + (derivative(t0) - derivative(t1)) * (dt * 0.25);
+            self.add_quad(c, b);
+        }
+    }
+
+    pub fn close_contour(&mut self) {
+        self.current_point = self.first_point;
+        self.contour_count = 0;
+    }
+
+    // Solid triangle wound the same way as every other one added this way, so that overlapping
+    // ones add up under the nonzero rule instead of cancelling out.
+    pub(crate) fn add_coverage_triangle(
+        &mut self,
+        a: Vector2<f32>,
+        b: Vector2<f32>,
+        c: Vector2<f32>,
+    ) {
+        let area = (b - a).perp(&(c - a));
+        if area == 0. {
+            return;
+        }
+        let (b, c) = if area > 0. { (b, c) } else { (c, b) };
+        self.add_triangle(a, b, c, COVERAGE_COLOR);
+        for &p in &[a, b, c] {
+            self.extend_bounds(p);
+        }
+    }
+
+    fn extend_bounds(&mut self, p: Vector2<f32>) {
+        self.glyph.bounding_box = Some(match self.glyph.bounding_box {
+            Some((min, max)) => (min.inf(&p), max.sup(&p)),
+            None => (p, p),
+        });
+    }
+
     pub fn build(self) -> Glyph {
         self.glyph
     }
@@ -127,51 +325,26 @@ impl GlyphBuilder {
 
 impl OutlineBuilder for GlyphBuilder {
     fn move_to(&mut self, x: f32, y: f32) {
-        self.first_point = Vector2::new(x, -y);
-        self.current_point = self.first_point;
-        self.contour_count = 0;
+        self.begin_contour(Vector2::new(x, -y));
     }
 
     fn line_to(&mut self, x: f32, y: f32) {
-        self.contour_count += 1;
-        let new_point = Vector2::new(x, -y);
-        if self.contour_count >= 2 {
-            self.add_triangle(
-                self.first_point,
-                self.current_point,
-                new_point,
-                Color::new(0., 0., 1. / 255., 1.),
-            );
-        }
-        self.current_point = new_point;
+        self.add_line(Vector2::new(x, -y));
     }
 
     fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
-        self.contour_count += 1;
-        let new_point = Vector2::new(x, -y);
-        if self.contour_count >= 2 {
-            self.add_triangle(
-                self.first_point,
-                self.current_point,
-                new_point,
-                Color::new(0., 0., 1. / 255., 1.),
-            );
-        }
-        self.add_curve(
-            self.current_point,
-            Vector2::new(x1, -y1),
-            new_point,
-            Color::new(0., 0., 1. / 255., 1.),
-        );
-        self.current_point = new_point;
+        self.add_quad(Vector2::new(x1, -y1), Vector2::new(x, -y));
     }
 
-    fn curve_to(&mut self, _x1: f32, _y1: f32, _x2: f32, _y2: f32, _x: f32, _y: f32) {
-        unimplemented!();
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.add_cubic(
+            Vector2::new(x1, -y1),
+            Vector2::new(x2, -y2),
+            Vector2::new(x, -y),
+        );
     }
 
     fn close(&mut self) {
-        self.current_point = self.first_point;
-        self.contour_count = 0;
+        self.close_contour();
     }
 }
@@ -0,0 +1,164 @@
+use nalgebra::{Affine2, Matrix3, Vector2};
+
+use crate::draw_list::{Color, DrawList, FillRule};
+
+// Stops beyond this are dropped; it matches the uniform arrays in `gradient.glsl`.
+pub const MAX_GRADIENT_STOPS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientKind {
+    Linear {
+        start: Vector2<f32>,
+        end: Vector2<f32>,
+    },
+    Radial {
+        center: Vector2<f32>,
+        radius: f32,
+    },
+    // `angle` is where offset 0 starts, sweeping clockwise in y-down coordinates
+    Conic {
+        center: Vector2<f32>,
+        angle: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpreadMode {
+    Pad,
+    Repeat,
+    Reflect,
+}
+
+#[derive(Debug, Clone)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub stops: Vec<(f32, Color)>,
+    pub spread: SpreadMode,
+    // maps the space `kind` is defined in to world space
+    pub transform: Affine2<f32>,
+}
+
+impl Gradient {
+    pub fn new(kind: GradientKind) -> Self {
+        Self {
+            kind,
+            stops: vec![],
+            spread: SpreadMode::Pad,
+            transform: Affine2::identity(),
+        }
+    }
+
+    pub fn linear(start: Vector2<f32>, end: Vector2<f32>) -> Self {
+        Self::new(GradientKind::Linear { start, end })
+    }
+
+    pub fn radial(center: Vector2<f32>, radius: f32) -> Self {
+        Self::new(GradientKind::Radial { center, radius })
+    }
+
+    pub fn conic(center: Vector2<f32>, angle: f32) -> Self {
+        Self::new(GradientKind::Conic { center, angle })
+    }
+
+    pub fn add_stop(mut self, offset: f32, col: Color) -> Self {
+        let i = self.stops.partition_point(|&(o, _)| o <= offset);
+        self.stops.insert(i, (offset, col));
+        self
+    }
+
+    pub fn with_spread(mut self, spread: SpreadMode) -> Self {
+        self.spread = spread;
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Affine2<f32>) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub(crate) fn kind_index(&self) -> i32 {
+        match self.kind {
+            GradientKind::Linear { .. } => 0,
+            GradientKind::Radial { .. } => 1,
+            GradientKind::Conic { .. } => 2,
+        }
+    }
+
+    pub(crate) fn spread_index(&self) -> i32 {
+        match self.spread {
+            SpreadMode::Pad => 0,
+            SpreadMode::Repeat => 1,
+            SpreadMode::Reflect => 2,
+        }
+    }
+
+    // Maps world space into a space where the gradient offset is x for linear gradients, the
+    // distance from the origin for radial ones and the angle around the origin for conic ones.
+    pub(crate) fn world_to_gradient(&self) -> Matrix3<f32> {
+        #[rustfmt::skip]
+        let normalize = match self.kind {
+            GradientKind::Linear { start, end } => {
+                let d = (end - start).unscale((end - start).norm_squared().max(f32::EPSILON));
+                Matrix3::new(
+                    d.x, d.y, -start.dot(&d),
+                    -d.y, d.x, start.x * d.y - start.y * d.x,
+                    0., 0., 1.,
+                )
+            }
+            GradientKind::Radial { center, radius } => {
+                Matrix3::new_scaling(1. / radius.max(f32::EPSILON))
+                    * Matrix3::new_translation(&-center)
+            }
+            GradientKind::Conic { center, angle } => {
+                Matrix3::new_rotation(-angle) * Matrix3::new_translation(&-center)
+            }
+        };
+        normalize * self.transform.inverse().to_homogeneous()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Paint {
+    Solid(Color),
+    Gradient(Gradient),
+}
+
+impl From<Color> for Paint {
+    fn from(col: Color) -> Self {
+        Paint::Solid(col)
+    }
+}
+
+impl From<Gradient> for Paint {
+    fn from(gradient: Gradient) -> Self {
+        Paint::Gradient(gradient)
+    }
+}
+
+impl DrawList {
+    // Vertex colors in the layer multiply the gradient, so shapes meant to show it as is should
+    // be added in white.
+    pub fn new_gradient_layer(&mut self, gradient: Gradient) {
+        self.new_layer();
+        self.set_gradient(gradient);
+    }
+
+    pub fn new_text_layer_with_paint(&mut self, paint: impl Into<Paint>, fill_rule: FillRule) {
+        match paint.into() {
+            Paint::Solid(col) => self.new_text_layer_with_fill_rule(col, fill_rule),
+            Paint::Gradient(gradient) => {
+                self.new_text_layer_with_fill_rule(Color::new(1., 1., 1., 1.), fill_rule);
+                self.set_gradient(gradient);
+            }
+        }
+    }
+
+    // Gradients are given in the coordinates of the current transform.
+    fn set_gradient(&mut self, mut gradient: Gradient) {
+        if let Some(transform) = self.transform_stack.last() {
+            gradient.transform = transform * gradient.transform;
+        }
+        self.gradients.push(gradient);
+        self.cmds.last_mut().unwrap().gradient = Some(self.gradients.len() - 1);
+    }
+}
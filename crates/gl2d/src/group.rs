@@ -0,0 +1,90 @@
+use nalgebra::Vector4;
+
+use crate::draw_list::{BlendMode, Color, DrawList, Vert};
+use crate::shadow::Shadow;
+
+// `Begin` redirects the layers up to the matching `End` to an offscreen target, which `End`
+// composites with its clip-space quad, textured over the whole target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupOp {
+    Begin,
+    End,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Group {
+    opacity: f32,
+    blend_mode: BlendMode,
+    // clip paths pushed before the group only apply when it is composited
+    pub(crate) clip_depth: usize,
+    shadow: Option<Shadow>,
+    // opened by `set_shadow` rather than `begin_group`
+    pub(crate) implicit: bool,
+}
+
+impl DrawList {
+    // Everything added until `end_group` is drawn as one layer, so translucent content doesn't
+    // show its own overlaps.
+    pub fn begin_group(&mut self, opacity: f32, blend_mode: BlendMode) {
+        self.begin_group_with_shadow(opacity, blend_mode, None);
+    }
+
+    pub fn begin_group_with_shadow(
+        &mut self,
+        opacity: f32,
+        blend_mode: BlendMode,
+        shadow: Option<Shadow>,
+    ) {
+        self.new_layer();
+        self.cmds.last_mut().unwrap().group_op = Some(GroupOp::Begin);
+        self.group_stack.push(Group {
+            opacity,
+            blend_mode,
+            clip_depth: self.clip_depth,
+            shadow,
+            implicit: false,
+        });
+        self.new_layer();
+    }
+
+    pub fn end_group(&mut self) {
+        // shadows set inside the group end with it
+        if matches!(self.group_stack.last(), Some(group) if group.implicit) {
+            self.close_group();
+        }
+        self.close_group();
+    }
+
+    // Ends the groups left open, shadows set without a matching `set_shadow(None)` included.
+    pub fn end_all_groups(&mut self) {
+        while !self.group_stack.is_empty() {
+            self.close_group();
+        }
+    }
+
+    pub(crate) fn close_group(&mut self) {
+        let group = match self.group_stack.pop() {
+            Some(group) => group,
+            None => return,
+        };
+        // clip paths left pushed inside the group end with it
+        self.clip_depth = group.clip_depth;
+        self.new_layer();
+        let cmd = self.cmds.last_mut().unwrap();
+        cmd.group_op = Some(GroupOp::End);
+        // the target holds premultiplied colors
+        cmd.blend_mode = group.blend_mode.premultiplied();
+        cmd.shadow = group.shadow;
+        let col = Color::repeat(group.opacity);
+        let idx = self.vtx_buffer.len() as u32;
+        for &(x, y) in [(-1., -1.), (1., -1.), (-1., 1.), (1., 1.)].iter() {
+            self.vtx_buffer.push(Vert::new(
+                Vector4::new(x, y, (x + 1.) * 0.5, (y + 1.) * 0.5),
+                col,
+            ));
+        }
+        self.push_elem(idx, idx + 1, idx + 2);
+        self.push_elem(idx + 1, idx + 2, idx + 3);
+        self.new_layer();
+    }
+}
@@ -0,0 +1,368 @@
+use anyhow::Result;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_SIGNATURE: &[u8] = &[0xff, 0xd8, 0xff];
+
+// Tightly packed RGBA8 pixels, ready for `GlowBackend::create_texture`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    // Decodes PNG or JPEG data, telling them apart by their signature.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        if bytes.starts_with(PNG_SIGNATURE) {
+            Self::decode_png(bytes)
+        } else if bytes.starts_with(JPEG_SIGNATURE) {
+            Self::decode_jpeg(bytes)
+        } else {
+            Err(anyhow::anyhow!("Unsupported image format"))
+        }
+    }
+
+    pub fn decode_png(bytes: &[u8]) -> Result<Self> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            png::ColorType::Grayscale => buffer.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+            png::ColorType::Indexed => {
+                return Err(anyhow::anyhow!("Indexed PNG was not expanded"));
+            }
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    pub fn decode_jpeg(bytes: &[u8]) -> Result<Self> {
+        let mut decoder = jpeg_decoder::Decoder::new(bytes);
+        let buffer = decoder.decode()?;
+        let info = decoder
+            .info()
+            .ok_or_else(|| anyhow::anyhow!("Missing JPEG image info"))?;
+        let pixels = match info.pixel_format {
+            jpeg_decoder::PixelFormat::RGB24 => buffer
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            jpeg_decoder::PixelFormat::L8 => buffer.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+            // big-endian 16-bit luminance, keep the high byte
+            jpeg_decoder::PixelFormat::L16 => buffer
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], 255])
+                .collect(),
+            jpeg_decoder::PixelFormat::CMYK32 => buffer
+                .chunks_exact(4)
+                .flat_map(|p| {
+                    let k = 255 - p[3] as u32;
+                    let channel = |c: u8| ((255 - c as u32) * k / 255) as u8;
+                    [channel(p[0]), channel(p[1]), channel(p[2]), 255]
+                })
+                .collect(),
+        };
+        Ok(Self {
+            width: info.width as u32,
+            height: info.height as u32,
+            pixels,
+        })
+    }
+
+    // Multiplies the color channels by the alpha, as textures hold premultiplied colors.
+    pub fn premultiply(&mut self) {
+        for p in self.pixels.chunks_exact_mut(4) {
+            let a = p[3] as u32;
+            for c in &mut p[..3] {
+                *c = ((*c as u32 * a + 127) / 255) as u8;
+            }
+        }
+    }
+
+    // Halves the image with a 2x2 box filter; the last row or column of an odd size is dropped.
+    pub fn downsample(&self) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let (y0, y1) = (y * 2, (y * 2 + 1).min(self.height - 1));
+            for x in 0..width {
+                let (x0, x1) = (x * 2, (x * 2 + 1).min(self.width - 1));
+                for c in 0..4 {
+                    let texel = |x: u32, y: u32| {
+                        self.pixels[((y * self.width + x) * 4 + c) as usize] as u32
+                    };
+                    let sum = texel(x0, y0) + texel(x1, y0) + texel(x0, y1) + texel(x1, y1);
+                    pixels.push(((sum + 2) / 4) as u8);
+                }
+            }
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    // The full mip chain, starting with a copy of this image.
+    pub fn mip_levels(&self) -> Vec<Self> {
+        let mut levels = vec![self.clone()];
+        loop {
+            let last = levels.last().unwrap();
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.downsample();
+            levels.push(next);
+        }
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_png(
+        width: u32,
+        height: u32,
+        color: png::ColorType,
+        depth: png::BitDepth,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut bytes = vec![];
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, width, height);
+            encoder.set_color(color);
+            encoder.set_depth(depth);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(data).unwrap();
+        }
+        bytes
+    }
+
+    // A baseline JPEG filled with one color, given as a sample per component (Y or YCbCr).
+    // Quantization is all ones and every block only holds its DC coefficient.
+    fn encode_solid_jpeg(width: u16, height: u16, samples: &[u8]) -> Vec<u8> {
+        fn segment(bytes: &mut Vec<u8>, marker: u8, body: &[u8]) {
+            bytes.extend([0xff, marker]);
+            bytes.extend(((body.len() + 2) as u16).to_be_bytes());
+            bytes.extend(body);
+        }
+        let mut bytes = vec![0xff, 0xd8];
+        let mut dqt = vec![0];
+        dqt.extend([1; 64]);
+        segment(&mut bytes, 0xdb, &dqt);
+        let mut sof = vec![8];
+        sof.extend(height.to_be_bytes());
+        sof.extend(width.to_be_bytes());
+        sof.push(samples.len() as u8);
+        for id in 1..=samples.len() as u8 {
+            sof.extend([id, 0x11, 0]);
+        }
+        segment(&mut bytes, 0xc0, &sof);
+        // DC sizes 0 to 11 all get 4-bit codes, the only AC symbol is end-of-block
+        let mut dht = vec![0x00, 0, 0, 0, 12];
+        dht.extend([0; 12]);
+        dht.extend(0..12);
+        segment(&mut bytes, 0xc4, &dht);
+        let mut dht = vec![0x10, 1];
+        dht.extend([0; 15]);
+        dht.push(0);
+        segment(&mut bytes, 0xc4, &dht);
+        let mut sos = vec![samples.len() as u8];
+        for id in 1..=samples.len() as u8 {
+            sos.extend([id, 0x00]);
+        }
+        sos.extend([0, 63, 0]);
+        segment(&mut bytes, 0xda, &sos);
+
+        let mut bits: Vec<bool> = vec![];
+        let mut put =
+            |value: u32, len: u32| bits.extend((0..len).rev().map(|i| value >> i & 1 == 1));
+        let blocks = ((width + 7) / 8) as usize * ((height + 7) / 8) as usize;
+        for block in 0..blocks {
+            for &sample in samples {
+                // later blocks repeat the first one's DC
+                let diff = if block == 0 {
+                    (sample as i32 - 128) * 8
+                } else {
+                    0
+                };
+                let size = 32 - diff.unsigned_abs().leading_zeros();
+                put(size, 4);
+                let value = if diff < 0 {
+                    diff + (1 << size) - 1
+                } else {
+                    diff
+                };
+                put(value as u32, size);
+                put(0, 1);
+            }
+        }
+        bits.resize((bits.len() + 7) / 8 * 8, true);
+        for byte in bits.chunks(8) {
+            let byte = byte.iter().fold(0, |byte, &bit| byte << 1 | bit as u8);
+            bytes.push(byte);
+            if byte == 0xff {
+                bytes.push(0);
+            }
+        }
+        bytes.extend([0xff, 0xd9]);
+        bytes
+    }
+
+    fn assert_pixels_near(image: &Image, expected: [u8; 4]) {
+        for pixel in image.pixels.chunks_exact(4) {
+            for (&a, &b) in pixel.iter().zip(expected.iter()) {
+                assert!(
+                    (a as i32 - b as i32).abs() <= 2,
+                    "{:?} != {:?}",
+                    pixel,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decode_png_rgba() {
+        let data = [255, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 0];
+        let bytes = encode_png(3, 1, png::ColorType::Rgba, png::BitDepth::Eight, &data);
+        let image = Image::decode(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (3, 1));
+        assert_eq!(image.pixels, data);
+    }
+
+    #[test]
+    fn decode_png_rgb() {
+        let data = [255, 0, 0, 10, 20, 30];
+        let bytes = encode_png(1, 2, png::ColorType::Rgb, png::BitDepth::Eight, &data);
+        let image = Image::decode(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (1, 2));
+        assert_eq!(image.pixels, [255, 0, 0, 255, 10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn decode_png_gray() {
+        let bytes = encode_png(
+            2,
+            1,
+            png::ColorType::Grayscale,
+            png::BitDepth::Eight,
+            &[7, 200],
+        );
+        let image = Image::decode(&bytes).unwrap();
+        assert_eq!(image.pixels, [7, 7, 7, 255, 200, 200, 200, 255]);
+
+        let data = [7, 100, 200, 0];
+        let bytes = encode_png(
+            2,
+            1,
+            png::ColorType::GrayscaleAlpha,
+            png::BitDepth::Eight,
+            &data,
+        );
+        let image = Image::decode(&bytes).unwrap();
+        assert_eq!(image.pixels, [7, 7, 7, 100, 200, 200, 200, 0]);
+    }
+
+    #[test]
+    fn decode_png_16_bit() {
+        let data = [0x12, 0x34, 0xab, 0xcd, 0xff, 0x00];
+        let bytes = encode_png(1, 1, png::ColorType::Rgb, png::BitDepth::Sixteen, &data);
+        let image = Image::decode(&bytes).unwrap();
+        assert_eq!(image.pixels, [0x12, 0xab, 0xff, 255]);
+    }
+
+    #[test]
+    fn decode_jpeg_rgb() {
+        // (200, 100, 50) in YCbCr
+        let bytes = encode_solid_jpeg(3, 2, &[124, 86, 182]);
+        let image = Image::decode(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.pixels.len(), 3 * 2 * 4);
+        assert_pixels_near(&image, [200, 100, 50, 255]);
+    }
+
+    #[test]
+    fn decode_jpeg_gray() {
+        let bytes = encode_solid_jpeg(9, 1, &[77]);
+        let image = Image::decode(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (9, 1));
+        assert_eq!(image.pixels.len(), 9 * 4);
+        assert_pixels_near(&image, [77, 77, 77, 255]);
+    }
+
+    #[test]
+    fn decode_rejects_unknown_signature() {
+        let error = Image::decode(b"GIF89a\x01\x00\x01\x00").unwrap_err();
+        assert_eq!(error.to_string(), "Unsupported image format");
+        assert!(Image::decode(&PNG_SIGNATURE[..4]).is_err());
+    }
+
+    #[test]
+    fn downsample_odd_size() {
+        let image = Image {
+            width: 3,
+            height: 3,
+            pixels: (0..9).flat_map(|i| [i * 10, 255 - i, 0, 255]).collect(),
+        };
+        let half = image.downsample();
+        assert_eq!((half.width, half.height), (1, 1));
+        // the average of the top-left 2x2 texels
+        assert_eq!(half.pixels, [20, 253, 0, 255]);
+
+        let image = Image {
+            width: 5,
+            height: 1,
+            pixels: (0..5).flat_map(|x| [x * 10, 0, 0, 255]).collect(),
+        };
+        let half = image.downsample();
+        assert_eq!((half.width, half.height), (2, 1));
+        // the fifth column is dropped
+        assert_eq!(half.pixels, [5, 0, 0, 255, 25, 0, 0, 255]);
+    }
+
+    #[test]
+    fn mip_levels_end_at_1x1() {
+        let image = Image {
+            width: 5,
+            height: 12,
+            pixels: vec![255; 5 * 12 * 4],
+        };
+        let sizes: Vec<_> = image
+            .mip_levels()
+            .iter()
+            .map(|level| (level.width, level.height))
+            .collect();
+        assert_eq!(sizes, [(5, 12), (2, 6), (1, 3), (1, 1)]);
+    }
+
+    #[test]
+    fn premultiply() {
+        let mut image = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![255, 128, 0, 128, 200, 100, 50, 0],
+        };
+        image.premultiply();
+        assert_eq!(image.pixels, [128, 64, 0, 128, 0, 0, 0, 0]);
+    }
+}
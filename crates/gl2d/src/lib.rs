@@ -1,19 +1,37 @@
 use draw_list::DrawContext;
-use nalgebra::Vector2;
+use nalgebra::{Affine2, Matrix3, Vector2};
 use owned_ttf_parser::Face;
 use wasm_bindgen::prelude::*;
 
 use crate::draw_list::{Color, DrawList};
 
-pub use crate::backend::GlowBackend;
-use crate::glyph::Glyph;
+pub use crate::backend::{BufferStats, GlowBackend};
+pub use crate::draw_list::BlendMode;
+use crate::glyph::GlyphCache;
+pub use crate::gradient::{Gradient, GradientKind, Paint, SpreadMode};
+pub use crate::image::Image;
 pub use crate::io::Io;
+pub use crate::path::{Path, PathBuilder, PathCmd, Polyline};
+pub use crate::sdf::SdfShape;
+pub use crate::shadow::Shadow;
+pub use crate::stroke::{LineCap, LineJoin, StrokeStyle};
+pub use crate::text_path::{TextAlign, TextPath};
+pub use crate::texture::{TextureFilter, TextureId, TextureOptions, TextureWrap};
 
 mod backend;
 mod draw_list;
 mod glyph;
+mod gradient;
+mod group;
+mod image;
 mod io;
+mod path;
+mod sdf;
+mod shadow;
 mod shape;
+mod stroke;
+mod text_path;
+mod texture;
 
 #[wasm_bindgen]
 extern "C" {
@@ -37,19 +55,21 @@ pub struct Gl2d {
     draw_list: DrawList,
     draw_context: DrawContext,
 
-    glyphs: Vec<Vec<(Glyph, f32)>>,
+    glyph_cache: GlyphCache,
+    glyphs: Vec<Vec<(usize, f32)>>,
 }
 
 #[wasm_bindgen]
 impl Gl2d {
     #[wasm_bindgen(constructor)]
-    pub fn new(backend: GlowBackend) -> Self {
+    pub fn new(mut backend: GlowBackend) -> Self {
         let draw_list = DrawList::new();
         let mut draw_context = DrawContext::new(Vector2::new(1000, 1000));
         draw_context.bg_color = Color::new(0., 0., 0., 0.);
         let transform = Default::default();
 
         let face = Face::from_slice(FONT_DATA, 0).unwrap();
+        let mut glyph_cache = GlyphCache::default();
         let glyphs = TEXT
             .split('\n')
             .map(|line| {
@@ -57,7 +77,7 @@ impl Gl2d {
                     .filter_map(|ch| {
                         if let Some(glyph_id) = face.glyph_index(ch) {
                             if let Some(hor_advance) = face.glyph_hor_advance(glyph_id) {
-                                let glyph = Glyph::new(&face, glyph_id);
+                                let glyph = glyph_cache.get_or_insert(&face, glyph_id);
                                 Some((glyph, hor_advance as f32))
                             } else {
                                 None
@@ -69,16 +89,71 @@ impl Gl2d {
                     .collect()
             })
             .collect();
+        backend.upload_glyphs(&glyph_cache).unwrap();
         Self {
             backend,
             transform,
             draw_list,
             draw_context,
 
+            glyph_cache,
             glyphs,
         }
     }
 
+    #[wasm_bindgen(getter = sdfShapes)]
+    pub fn sdf_shapes(&self) -> bool {
+        self.draw_list.sdf_shapes
+    }
+
+    #[wasm_bindgen(setter = sdfShapes)]
+    pub fn set_sdf_shapes(&mut self, sdf_shapes: bool) {
+        self.draw_list.sdf_shapes = sdf_shapes;
+    }
+
+    // Buffer uploads of the last frame.
+    #[wasm_bindgen(getter = bufferStats)]
+    pub fn buffer_stats(&self) -> BufferStats {
+        self.backend.buffer_stats()
+    }
+
+    // Decodes PNG or JPEG data into a new texture, returning its id for `drawImage` and
+    // `deleteImage`.
+    #[wasm_bindgen(js_name = loadImage)]
+    pub fn load_image(&mut self, bytes: &[u8], mipmaps: bool) -> Result<u32, JsValue> {
+        let options = TextureOptions {
+            mipmaps,
+            ..Default::default()
+        };
+        self.backend
+            .load_image(bytes, options)
+            .map(|id| id.0)
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = deleteImage)]
+    pub fn delete_image(&mut self, id: u32) {
+        self.backend.delete_texture(TextureId(id));
+    }
+
+    // Draws the whole image into the rectangle at `x, y`, in world units, between `begin_frame`
+    // and `draw`.
+    #[wasm_bindgen(js_name = drawImage)]
+    pub fn draw_image(&mut self, id: u32, x: f32, y: f32, width: f32, height: f32) {
+        let min = Vector2::new(x, y);
+        self.draw_list.add_image(
+            TextureId(id),
+            (min, min + Vector2::new(width, height)),
+            (Vector2::zeros(), Vector2::repeat(1.)),
+            Color::new(1., 1., 1., 1.),
+        );
+    }
+
+    // Deletes the GL objects of the backend; `free` does it as well.
+    pub fn destroy(&mut self) {
+        self.backend.destroy();
+    }
+
     pub fn begin_frame(&mut self, io: &mut io::Io) {
         self.transform.screen_size = io.screen_size;
         let pixel_ratio = io.pixel_ratio;
@@ -99,26 +174,32 @@ impl Gl2d {
         self.draw_context.screen_size = self.transform.screen_size;
     }
 
-    pub fn draw(&mut self) {
+    // Fails once the backend is destroyed.
+    pub fn draw(&mut self) -> Result<(), JsValue> {
         self.draw_list.new_text_layer(Color::new(0., 0., 0., 1.0));
 
         let scale = 0.2f32;
         let line_height = 1000.0 * scale;
         for (lineno, line) in self.glyphs.iter().enumerate() {
             let mut x = 0f32;
-            for (glyph, hor_advance) in line {
-                self.draw_list.add_glyph(
+            for &(glyph, hor_advance) in line {
+                let position = Vector2::new(x, line_height * (lineno + 1) as f32);
+                let transform = Affine2::from_matrix_unchecked(
+                    Matrix3::new_translation(&position) * Matrix3::new_scaling(scale),
+                );
+                self.draw_list.add_glyph_instance(
                     &self.draw_context,
-                    Vector2::new(x, line_height * (lineno + 1) as f32),
-                    scale,
+                    &transform,
+                    &self.glyph_cache,
                     glyph,
+                    Color::new(0., 0., 0., 1.),
                 );
                 x += hor_advance * scale;
             }
         }
         self.backend
-            .draw(&self.draw_context, &self.draw_list)
-            .unwrap();
+            .draw(&self.draw_context, &mut self.draw_list)
+            .map_err(|err| JsValue::from_str(&err.to_string()))
     }
 }
 
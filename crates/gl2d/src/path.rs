@@ -0,0 +1,279 @@
+use nalgebra::{Affine2, Vector2};
+
+use crate::draw_list::{
+    arc_quad_count, cubic_segment_count, quad_segment_count, DrawContext, DrawList, FillRule,
+    DEFAULT_TOLERANCE,
+};
+use crate::glyph::{Glyph, GlyphBuilder};
+use crate::gradient::Paint;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathCmd {
+    MoveTo(Vector2<f32>),
+    LineTo(Vector2<f32>),
+    QuadTo(Vector2<f32>, Vector2<f32>),
+    CubicTo(Vector2<f32>, Vector2<f32>, Vector2<f32>),
+    Close,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Path {
+    cmds: Vec<PathCmd>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Polyline {
+    pub points: Vec<Vector2<f32>>,
+    pub closed: bool,
+}
+
+impl Polyline {
+    fn push(&mut self, p: Vector2<f32>) {
+        if self.points.last() != Some(&p) {
+            self.points.push(p);
+        }
+    }
+}
+
+impl Path {
+    pub fn cmds(&self) -> &[PathCmd] {
+        &self.cmds
+    }
+
+    // Cubics become quadratics no further than `tolerance` away.
+    pub fn to_fill_mesh(&self, tolerance: f32) -> Glyph {
+        let mut builder = GlyphBuilder::new(tolerance);
+        for cmd in &self.cmds {
+            match *cmd {
+                PathCmd::MoveTo(p) => builder.begin_contour(p),
+                PathCmd::LineTo(p) => builder.add_line(p),
+                PathCmd::QuadTo(c, p) => builder.add_quad(c, p),
+                PathCmd::CubicTo(c1, c2, p) => builder.add_cubic(c1, c2, p),
+                PathCmd::Close => builder.close_contour(),
+            }
+        }
+        builder.build()
+    }
+
+    // Splits the path into subpaths of straight segments, no further than `tolerance` away from
+    // the curves.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Polyline> {
+        let mut polylines = vec![];
+        let mut current = Polyline::default();
+        let mut last = Vector2::zeros();
+        for cmd in &self.cmds {
+            match *cmd {
+                PathCmd::MoveTo(p) => {
+                    if current.points.len() > 1 {
+                        polylines.push(std::mem::take(&mut current));
+                    }
+                    current.points.clear();
+                    current.points.push(p);
+                    last = p;
+                }
+                PathCmd::LineTo(p) => {
+                    current.push(p);
+                    last = p;
+                }
+                PathCmd::QuadTo(c, p) => {
+                    let segment_count = quad_segment_count(last, c, p, tolerance);
+                    for i in 1..=segment_count {
+                        let t = i as f32 / segment_count as f32;
+                        let mt = 1. - t;
+                        current.push(last * (mt * mt) + c * (2. * mt * t) + p * (t * t));
+                    }
+                    last = p;
+                }
+                PathCmd::CubicTo(c1, c2, p) => {
+                    let segment_count = cubic_segment_count(last, c1, c2, p, tolerance);
+                    for i in 1..=segment_count {
+                        let t = i as f32 / segment_count as f32;
+                        let mt = 1. - t;
+                        current.push(
+                            last * (mt * mt * mt)
+                                + c1 * (3. * mt * mt * t)
+                                + c2 * (3. * mt * t * t)
+                                + p * (t * t * t),
+                        );
+                    }
+                    last = p;
+                }
+                PathCmd::Close => {
+                    if current.points.len() > 1 && current.points.first() == current.points.last() {
+                        current.points.pop();
+                    }
+                    if let Some(&first) = current.points.first() {
+                        current.closed = true;
+                        polylines.push(std::mem::take(&mut current));
+                        current.points.push(first);
+                        last = first;
+                    }
+                }
+            }
+        }
+        if current.points.len() > 1 {
+            polylines.push(current);
+        }
+        polylines
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PathBuilder {
+    path: Path,
+    first_point: Vector2<f32>,
+    current_point: Option<Vector2<f32>>,
+    tolerance: f32,
+}
+
+impl PathBuilder {
+    pub fn new() -> Self {
+        Self {
+            path: Path::default(),
+            first_point: Vector2::zeros(),
+            current_point: None,
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    // Maximum distance between the arcs added afterwards and the quadratics approximating them,
+    // e.g. `DrawList::tolerance`.
+    pub fn set_tolerance(&mut self, tolerance: f32) -> &mut Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn move_to(&mut self, p: Vector2<f32>) -> &mut Self {
+        self.path.cmds.push(PathCmd::MoveTo(p));
+        self.first_point = p;
+        self.current_point = Some(p);
+        self
+    }
+
+    pub fn line_to(&mut self, p: Vector2<f32>) -> &mut Self {
+        if self.current_point.is_none() {
+            return self.move_to(p);
+        }
+        self.path.cmds.push(PathCmd::LineTo(p));
+        self.current_point = Some(p);
+        self
+    }
+
+    pub fn quad_to(&mut self, c: Vector2<f32>, p: Vector2<f32>) -> &mut Self {
+        if self.current_point.is_none() {
+            self.move_to(c);
+        }
+        self.path.cmds.push(PathCmd::QuadTo(c, p));
+        self.current_point = Some(p);
+        self
+    }
+
+    pub fn cubic_to(&mut self, c1: Vector2<f32>, c2: Vector2<f32>, p: Vector2<f32>) -> &mut Self {
+        if self.current_point.is_none() {
+            self.move_to(c1);
+        }
+        self.path.cmds.push(PathCmd::CubicTo(c1, c2, p));
+        self.current_point = Some(p);
+        self
+    }
+
+    // Same semantics as the canvas `arcTo`: a line towards `p1`, then an arc of `radius` tangent
+    // to both p0-p1 and p1-p2.
+    pub fn arc_to(&mut self, p1: Vector2<f32>, p2: Vector2<f32>, radius: f32) -> &mut Self {
+        let p0 = match self.current_point {
+            Some(p0) => p0,
+            None => return self.move_to(p1),
+        };
+        let (d0, d1) = match ((p0 - p1).try_normalize(0.), (p2 - p1).try_normalize(0.)) {
+            (Some(d0), Some(d1)) => (d0, d1),
+            _ => return self.line_to(p1),
+        };
+        let cos = d0.dot(&d1).clamp(-1., 1.);
+        if radius <= 0. || 1. - cos.abs() < 1e-6 {
+            return self.line_to(p1);
+        }
+        let half_angle = cos.acos() * 0.5;
+        let tangent_distance = radius / half_angle.tan();
+        let center = p1 + (d0 + d1).normalize() * (radius / half_angle.sin());
+        let t0 = p1 + d0 * tangent_distance;
+        let t1 = p1 + d1 * tangent_distance;
+        let start_angle = (t0.y - center.y).atan2(t0.x - center.x);
+        let mut sweep = (t1.y - center.y).atan2(t1.x - center.x) - start_angle;
+        if sweep > std::f32::consts::PI {
+            sweep -= 2. * std::f32::consts::PI;
+        } else if sweep < -std::f32::consts::PI {
+            sweep += 2. * std::f32::consts::PI;
+        }
+        self.arc(center, radius, start_angle, start_angle + sweep)
+    }
+
+    // Connects the current point to the start of the arc with a line, like the canvas `arc`,
+    // which also draws sweeps past a full turn as a full circle.
+    pub fn arc(
+        &mut self,
+        center: Vector2<f32>,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+    ) -> &mut Self {
+        let at = |angle: f32| center + Vector2::new(angle.cos(), angle.sin()) * radius;
+        self.line_to(at(start_angle));
+        let full_turn = 2. * std::f32::consts::PI;
+        let sweep = (end_angle - start_angle).clamp(-full_turn, full_turn);
+        let segment_count = arc_quad_count(radius, sweep, self.tolerance);
+        let segment_angle = sweep / segment_count as f32;
+        let control_radius = radius / (segment_angle * 0.5).cos();
+        for i in 0..segment_count {
+            let angle = start_angle + segment_angle * i as f32;
+            let mid = angle + segment_angle * 0.5;
+            let c = center + Vector2::new(mid.cos(), mid.sin()) * control_radius;
+            self.quad_to(c, at(angle + segment_angle));
+        }
+        self
+    }
+
+    pub fn close(&mut self) -> &mut Self {
+        if self.current_point.is_some() {
+            self.path.cmds.push(PathCmd::Close);
+            self.current_point = Some(self.first_point);
+        }
+        self
+    }
+
+    pub fn build(&mut self) -> Path {
+        self.current_point = None;
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Default for PathBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DrawList {
+    // Fills go through the text coverage pass, so the path gets a text layer of its own and a
+    // fresh regular layer is opened afterwards.
+    pub fn fill_path(
+        &mut self,
+        ctx: &DrawContext,
+        path: &Path,
+        paint: impl Into<Paint>,
+        fill_rule: FillRule,
+    ) {
+        let mesh = path.to_fill_mesh(self.tolerance(ctx));
+        self.new_text_layer_with_paint(paint, fill_rule);
+        self.add_glyph(ctx, &Affine2::identity(), &mesh);
+        self.new_layer();
+    }
+
+    // Clips everything drawn until the matching `pop_clip_path` to the inside of `path`,
+    // intersected with the clip paths already pushed.
+    pub fn push_clip_path(&mut self, ctx: &DrawContext, path: &Path, fill_rule: FillRule) {
+        let mesh = path.to_fill_mesh(self.tolerance(ctx));
+        self.push_clip_layer(fill_rule);
+        self.add_glyph(ctx, &Affine2::identity(), &mesh);
+        self.new_layer();
+    }
+}
@@ -0,0 +1,87 @@
+use nalgebra::{Affine2, Point2, Vector2};
+
+use crate::draw_list::{Color, DrawContext, DrawList};
+
+// A rounded box drawn as a single quad and evaluated in the fragment shader. Circles and capsules
+// are boxes whose corner radii reach their half size.
+#[derive(Debug, Clone)]
+pub struct SdfShape {
+    pub center: Vector2<f32>,
+    pub axis: Vector2<f32>,
+    pub half_size: Vector2<f32>,
+    // clockwise from the top-left corner
+    pub radii: [f32; 4],
+    // zero fills the shape, anything else strokes an outline centered on its edge
+    pub thickness: f32,
+    pub col: Color,
+}
+
+impl SdfShape {
+    pub fn rect(
+        center: Vector2<f32>,
+        half_size: Vector2<f32>,
+        radii: [f32; 4],
+        col: Color,
+    ) -> Self {
+        Self {
+            center,
+            axis: Vector2::new(1., 0.),
+            half_size,
+            radii,
+            thickness: 0.,
+            col,
+        }
+    }
+
+    pub fn circle(center: Vector2<f32>, r: f32, col: Color) -> Self {
+        Self::rect(center, Vector2::new(r, r), [r; 4], col)
+    }
+
+    pub fn capsule(p1: Vector2<f32>, p2: Vector2<f32>, r: f32, col: Color) -> Self {
+        let d = p2 - p1;
+        let length = d.norm();
+        Self {
+            center: (p1 + p2) * 0.5,
+            axis: d.try_normalize(0.).unwrap_or_else(|| Vector2::new(1., 0.)),
+            half_size: Vector2::new(length * 0.5 + r, r),
+            radii: [r; 4],
+            thickness: 0.,
+            col,
+        }
+    }
+
+    pub fn with_thickness(mut self, thickness: f32) -> Self {
+        self.thickness = thickness;
+        self
+    }
+
+    // Lengths scale by the square root of the determinant, which is exact for transforms
+    // without skew or non-uniform scaling.
+    pub(crate) fn transformed(mut self, transform: &Affine2<f32>) -> Self {
+        let m = transform.matrix();
+        let linear = m.fixed_slice::<2, 2>(0, 0);
+        let scale = linear.determinant().abs().sqrt();
+        self.center = (transform * Point2::from(self.center)).coords;
+        self.axis = (linear * self.axis).try_normalize(0.).unwrap_or(self.axis);
+        self.half_size *= scale;
+        self.radii = self.radii.map(|r| r * scale);
+        self.thickness *= scale;
+        self
+    }
+
+    pub(crate) fn bounds(&self) -> (Vector2<f32>, Vector2<f32>) {
+        let e = self.half_size.add_scalar(self.thickness * 0.5);
+        let (ax, ay) = (self.axis.x.abs(), self.axis.y.abs());
+        let extent = Vector2::new(ax * e.x + ay * e.y, ay * e.x + ax * e.y);
+        (self.center - extent, self.center + extent)
+    }
+}
+
+impl DrawList {
+    pub fn add_sdf_shape(&mut self, ctx: &DrawContext, shape: SdfShape) {
+        let (min, max) = shape.bounds();
+        if self.is_visible(ctx, min, max) {
+            self.push_sdf_shape(shape);
+        }
+    }
+}
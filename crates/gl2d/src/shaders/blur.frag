@@ -0,0 +1,27 @@
+precision mediump float;
+
+#define MAX_TAPS 16
+
+uniform sampler2D texture;
+// texture coordinates per pixel along the blurred axis
+uniform vec2 direction;
+uniform float tap_spacing;
+uniform float sigma;
+uniform vec4 color;
+// turns the blurred alpha into `color` instead of blurring all channels
+uniform bool alpha_only;
+varying vec4 frag_color;
+varying vec2 uv;
+
+void main() {
+    vec4 sum = vec4(0.0);
+    float total = 0.0;
+    for (int i = -MAX_TAPS; i <= MAX_TAPS; i++) {
+        float x = float(i) * tap_spacing;
+        float weight = exp(-x * x / (2.0 * sigma * sigma));
+        sum += texture2D(texture, uv + direction * x) * weight;
+        total += weight;
+    }
+    sum /= total;
+    gl_FragColor = alpha_only ? color * (sum.a * frag_color.a) : sum;
+}
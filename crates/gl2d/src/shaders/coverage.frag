@@ -0,0 +1,17 @@
+precision mediump float;
+
+varying vec4 frag_color;
+varying vec2 coord2;
+
+void main() {
+    if (coord2.x * coord2.x - coord2.y > 0.0) {
+        discard;
+    }
+    // r/g count front/back facing triangles for nonzero, b counts all of them for even-odd
+    float unit = frag_color.z;
+    if (gl_FrontFacing) {
+        gl_FragColor = vec4(unit, 0.0, unit, frag_color.w);
+    } else {
+        gl_FragColor = vec4(0.0, unit, unit, frag_color.w);
+    }
+}
@@ -0,0 +1,21 @@
+attribute vec2 vert_position;
+attribute vec2 vert_uv;
+attribute vec4 vert_color;
+attribute vec2 inst_x_axis;
+attribute vec2 inst_y_axis;
+attribute vec2 inst_offset;
+attribute vec4 inst_color;
+uniform   mat4 projection;
+varying   vec4 frag_color;
+varying   vec2 coord2;
+
+void main() {
+    vec2 xy = mat2(inst_x_axis, inst_y_axis) * vert_position + inst_offset;
+    gl_Position = projection * vec4(xy, 0, 1);
+#ifdef INSTANCE_COLOR
+    frag_color = inst_color;
+#else
+    frag_color = vert_color;
+#endif
+    coord2 = vert_uv;
+}
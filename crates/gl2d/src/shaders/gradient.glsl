@@ -0,0 +1,39 @@
+precision highp float;
+
+uniform int   gradient_kind;
+uniform int   gradient_spread;
+uniform int   stop_count;
+uniform float stop_offsets[8];
+uniform vec4  stop_colors[8];
+varying vec2  gradient_coord;
+
+vec4 gradient_color() {
+    float t;
+    if (gradient_kind == 1) {
+        t = length(gradient_coord);
+    } else if (gradient_kind == 2) {
+        t = fract(atan(gradient_coord.y, gradient_coord.x) / 6.28318530718);
+    } else {
+        t = gradient_coord.x;
+    }
+    if (gradient_spread == 1) {
+        t = fract(t);
+    } else if (gradient_spread == 2) {
+        t = 1.0 - abs(mod(t, 2.0) - 1.0);
+    }
+
+    vec4 color = stop_colors[0];
+    for (int i = 1; i < 8; i++) {
+        if (i >= stop_count) {
+            break;
+        }
+        float start = stop_offsets[i - 1];
+        float end = stop_offsets[i];
+        if (t >= end) {
+            color = stop_colors[i];
+        } else if (t > start) {
+            color = mix(stop_colors[i - 1], stop_colors[i], (t - start) / (end - start));
+        }
+    }
+    return color;
+}
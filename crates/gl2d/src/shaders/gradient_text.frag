@@ -0,0 +1,16 @@
+
+uniform sampler2D texture;
+uniform int fill_rule;
+varying vec4 frag_color;
+varying vec2 coord2;
+
+void main() {
+    vec4 count = texture2D(texture, coord2) * 255.0;
+    float coverage;
+    if (fill_rule == 1) {
+        coverage = step(0.5, abs(count.x - count.y));
+    } else {
+        coverage = mod(count.z, 2.0);
+    }
+    gl_FragColor = gradient_color() * frag_color * coverage;
+}
@@ -0,0 +1,15 @@
+attribute vec2 vert_position;
+attribute vec4 vert_color;
+uniform   mat4 projection;
+uniform   vec2 coord_scale;
+uniform   mat3 gradient_transform;
+varying   vec4 frag_color;
+varying   vec2 coord2;
+varying   vec2 gradient_coord;
+
+void main() {
+    gl_Position = projection * vec4(vert_position, 0, 1);
+    frag_color = vert_color;
+    coord2 = (gl_Position.xy * 0.5 + 0.5) * coord_scale;
+    gradient_coord = (gradient_transform * vec3(vert_position, 1)).xy;
+}
@@ -0,0 +1,9 @@
+precision mediump float;
+
+uniform sampler2D texture;
+varying vec4 frag_color;
+varying vec2 uv;
+
+void main() {
+    gl_FragColor = texture2D(texture, uv) * frag_color;
+}
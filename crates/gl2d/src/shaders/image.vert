@@ -0,0 +1,14 @@
+attribute vec2 vert_position;
+attribute vec2 vert_uv;
+attribute vec4 vert_color;
+uniform   mat4 projection;
+uniform   vec2 uv_offset;
+uniform   vec2 uv_scale;
+varying   vec4 frag_color;
+varying   vec2 uv;
+
+void main() {
+    gl_Position = projection * vec4(vert_position, 0, 1);
+    frag_color = vert_color;
+    uv = uv_offset + vert_uv * uv_scale;
+}
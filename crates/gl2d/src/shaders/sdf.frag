@@ -0,0 +1,34 @@
+precision highp float;
+
+uniform float pixel_size;
+varying vec4 frag_color;
+varying vec2 local;
+varying vec2 half_size;
+varying vec4 radii;
+varying float thickness;
+
+// radii go clockwise from the top-left corner, y pointing down
+float rounded_box(vec2 p, vec2 b, vec4 r4) {
+    float r = p.x < 0.0 ? (p.y < 0.0 ? r4.x : r4.w) : (p.y < 0.0 ? r4.y : r4.z);
+    r = min(r, min(b.x, b.y));
+    vec2 q = abs(p) - b + r;
+    return length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - r;
+}
+
+void main() {
+    float d;
+    if (thickness > 0.0) {
+        float h = thickness * 0.5;
+        vec4 outer = radii + step(1e-4, radii) * h;
+        vec4 inner = max(radii - h, 0.0);
+        d = max(rounded_box(local, half_size + h, outer),
+                -rounded_box(local, max(half_size - h, 0.0), inner));
+    } else {
+        d = rounded_box(local, half_size, radii);
+    }
+    float alpha = clamp(0.5 - d / pixel_size, 0.0, 1.0);
+    if (alpha <= 0.0) {
+        discard;
+    }
+    gl_FragColor = frag_color * alpha;
+}
@@ -0,0 +1,26 @@
+attribute vec2 vert_position;
+attribute vec4 vert_color;
+attribute vec2 inst_center;
+attribute vec2 inst_axis;
+attribute vec2 inst_half_size;
+attribute vec4 inst_radii;
+attribute float inst_thickness;
+attribute vec4 inst_color;
+uniform   mat4 projection;
+uniform   float pixel_size;
+varying   vec4 frag_color;
+varying   vec2 local;
+varying   vec2 half_size;
+varying   vec4 radii;
+varying   float thickness;
+
+void main() {
+    // one extra pixel around the shape leaves room for the anti-aliased edge
+    local = vert_position * (inst_half_size + inst_thickness * 0.5 + pixel_size);
+    vec2 xy = inst_center + inst_axis * local.x + vec2(-inst_axis.y, inst_axis.x) * local.y;
+    gl_Position = projection * vec4(xy, 0, 1);
+    frag_color = vert_color * inst_color;
+    half_size = inst_half_size;
+    radii = inst_radii;
+    thickness = inst_thickness;
+}
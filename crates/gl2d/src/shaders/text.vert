@@ -1,11 +1,12 @@
-attribute vec4 vert_position;
+attribute vec2 vert_position;
 attribute vec4 vert_color;
 uniform   mat4 projection;
+uniform   vec2 coord_scale;
 varying   vec4 frag_color;
 varying   vec2 coord2;
 
 void main() {
-    gl_Position = projection * vec4(vert_position.xy, 0, 1);
+    gl_Position = projection * vec4(vert_position, 0, 1);
     frag_color = vert_color;
-    coord2 = gl_Position.xy * 0.5 + 0.5;
+    coord2 = (gl_Position.xy * 0.5 + 0.5) * coord_scale;
 }
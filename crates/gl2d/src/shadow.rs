@@ -0,0 +1,39 @@
+use nalgebra::Vector2;
+
+use crate::draw_list::{BlendMode, Color, DrawList};
+
+// Blurred copy of the content's alpha, tinted with `col` and drawn beneath it at `offset`.
+// `radius` is about twice the standard deviation of the blur.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shadow {
+    pub radius: f32,
+    pub offset: Vector2<f32>,
+    pub col: Color,
+}
+
+impl Shadow {
+    pub fn new(radius: f32, offset: Vector2<f32>, col: Color) -> Self {
+        Self {
+            radius,
+            offset,
+            col,
+        }
+    }
+}
+
+impl DrawList {
+    // Shapes and text layers added while a shadow is set are drawn as a group casting it, so
+    // overlapping content casts a single shadow. A shadow set before `begin_group` encloses the
+    // group, so it can't change before the matching `end_group`; calls in between are ignored.
+    pub fn set_shadow(&mut self, shadow: Option<Shadow>) {
+        match self.group_stack.last() {
+            Some(group) if group.implicit => self.close_group(),
+            _ if self.group_stack.iter().any(|group| group.implicit) => return,
+            _ => {}
+        }
+        if let Some(shadow) = shadow {
+            self.begin_group_with_shadow(1., BlendMode::Normal, Some(shadow));
+            self.group_stack.last_mut().unwrap().implicit = true;
+        }
+    }
+}
@@ -1,33 +1,130 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::rc::Rc;
+
 use nalgebra::{Vector2, Vector4};
 
-use crate::draw_list::{Color, DrawList, Vert, DrawContext};
+use crate::draw_list::{
+    arc_segment_count, points_bounds, Color, DrawContext, DrawList, Vert, TOLERANCE_PX,
+};
+use crate::path::Polyline;
+use crate::sdf::SdfShape;
+use crate::stroke::StrokeStyle;
 
-pub struct LineParams {
-    half_thickness: f32,
-    cap_segments: Vec<Vector2<f32>>,
+fn unit_circle_points(segment_count: usize) -> Rc<[Vector2<f32>]> {
+    (0..=segment_count)
+        .map(|i| {
+            let rad = i as f32 * 2.0 / segment_count as f32 * PI;
+            Vector2::new(rad.cos(), rad.sin())
+        })
+        .collect()
 }
 
-impl LineParams {
-    pub fn new(scale: f32, thickness: f32) -> Self {
-        let resolution = thickness * scale;
-        let cap_segment_count = if resolution <= 1.0 {
-            0
-        } else {
-            (resolution * 1.5).ceil() as usize
-        };
-        let half_thickness = thickness * 0.5;
-        let cap_segments = (0..=cap_segment_count)
-            .map(|i| {
-                let rad = i as f32 * 2.0 / cap_segment_count as f32 * std::f32::consts::PI;
-                Vector2::new(rad.cos(), rad.sin()).scale(half_thickness)
+// Unit circles tessellated for radii bucketed by half octaves of device pixels, so zooming only
+// retessellates when a circle crosses into another bucket.
+#[derive(Debug, Clone, Default)]
+pub struct CircleCache {
+    tables: HashMap<i32, Rc<[Vector2<f32>]>>,
+}
+
+impl CircleCache {
+    pub fn get(&mut self, ctx: &DrawContext, radius: f32) -> Rc<[Vector2<f32>]> {
+        let radius_px = radius * ctx.scale * ctx.pixel_ratio;
+        let bucket = (radius_px.max(1.).log2() * 2.).ceil() as i32;
+        self.tables
+            .entry(bucket)
+            .or_insert_with(|| {
+                let bucket_radius = (bucket as f32 * 0.5).exp2();
+                unit_circle_points(arc_segment_count(bucket_radius, 2. * PI, TOLERANCE_PX).max(3))
             })
-            .collect();
-        Self {
-            half_thickness,
-            cap_segments,
+            .clone()
+    }
+}
+
+fn push_arc(
+    points: &mut Vec<Vector2<f32>>,
+    center: Vector2<f32>,
+    radii: Vector2<f32>,
+    start_angle: f32,
+    end_angle: f32,
+    segment_count: usize,
+) {
+    let step = (end_angle - start_angle) / segment_count as f32;
+    for i in 0..=segment_count {
+        let (sin, cos) = (start_angle + step * i as f32).sin_cos();
+        points.push(center + Vector2::new(cos * radii.x, sin * radii.y));
+    }
+}
+
+// Scales the corner radii down together until neighbouring corners no longer overlap.
+fn fit_radii(size: Vector2<f32>, radii: [f32; 4]) -> [f32; 4] {
+    let [tl, tr, br, bl] = radii.map(|r| r.max(0.));
+    let fit = [
+        size.x / (tl + tr),
+        size.y / (tr + br),
+        size.x / (br + bl),
+        size.y / (bl + tl),
+    ]
+    .iter()
+    .fold(1.0f32, |a, &b| if b.is_nan() { a } else { a.min(b) });
+    [tl * fit, tr * fit, br * fit, bl * fit]
+}
+
+// `scale` is that of the draw list transform, which the arcs are tessellated for.
+fn rounded_rect_points(
+    ctx: &DrawContext,
+    scale: f32,
+    p: Vector2<f32>,
+    size: Vector2<f32>,
+    radii: [f32; 4],
+) -> Vec<Vector2<f32>> {
+    use std::f32::consts::FRAC_PI_2;
+
+    let half_size = size * 0.5;
+    let [tl, tr, br, bl] = fit_radii(size, radii);
+    let corners = [
+        (Vector2::new(-half_size.x + tl, -half_size.y + tl), tl, PI),
+        (
+            Vector2::new(half_size.x - tr, -half_size.y + tr),
+            tr,
+            PI + FRAC_PI_2,
+        ),
+        (Vector2::new(half_size.x - br, half_size.y - br), br, 0.),
+        (
+            Vector2::new(-half_size.x + bl, half_size.y - bl),
+            bl,
+            FRAC_PI_2,
+        ),
+    ];
+    let mut points = vec![];
+    for &(center, r, start_angle) in corners.iter() {
+        if r > 0. {
+            let segment_count = ctx.arc_segment_count(r * scale, FRAC_PI_2);
+            push_arc(
+                &mut points,
+                p + center,
+                Vector2::new(r, r),
+                start_angle,
+                start_angle + FRAC_PI_2,
+                segment_count,
+            );
+        } else {
+            points.push(p + center);
         }
     }
+    points.dedup();
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    points
+}
+
+pub struct LineParams {
+    half_thickness: f32,
+    cap_segments: Vec<Vector2<f32>>,
+}
 
+impl LineParams {
     fn vtx_count(&self) -> usize {
         4 + self.cap_segments.len()
     }
@@ -38,6 +135,49 @@ impl LineParams {
 }
 
 impl DrawList {
+    // Line parameters for the current zoom and transform.
+    pub fn line_params(&self, ctx: &DrawContext, thickness: f32) -> LineParams {
+        let half_thickness = thickness * 0.5;
+        let cap_segments =
+            if thickness * ctx.scale * ctx.pixel_ratio * self.transform_scale() <= 1.0 {
+                vec![]
+            } else {
+                let segment_count =
+                    arc_segment_count(half_thickness, 2. * PI, self.tolerance(ctx)).max(3);
+                unit_circle_points(segment_count)
+                    .iter()
+                    .map(|v| v.scale(half_thickness))
+                    .collect()
+            };
+        LineParams {
+            half_thickness,
+            cap_segments,
+        }
+    }
+
+    // Unit circle sized for `radius` at the current zoom and transform, closed by repeating its
+    // first point.
+    pub fn unit_circle(&mut self, ctx: &DrawContext, radius: f32) -> Rc<[Vector2<f32>]> {
+        let radius = radius * self.transform_scale();
+        match &mut self.circle_cache {
+            Some(cache) => cache.get(ctx, radius),
+            None => unit_circle_points(ctx.arc_segment_count(radius, 2. * PI).max(3)),
+        }
+    }
+
+    fn ellipse_points(
+        &mut self,
+        ctx: &DrawContext,
+        p: Vector2<f32>,
+        radii: Vector2<f32>,
+    ) -> Vec<Vector2<f32>> {
+        let unit_circle = self.unit_circle(ctx, radii.x.max(radii.y));
+        unit_circle[1..]
+            .iter()
+            .map(|v| p + v.component_mul(&radii))
+            .collect()
+    }
+
     pub fn add_line_with_params(
         &mut self,
         p1: Vector2<f32>,
@@ -45,28 +185,20 @@ impl DrawList {
         col: Color,
         params: &LineParams,
     ) {
+        let margin = Vector2::repeat(params.half_thickness);
+        if !self.is_inside_clip(p1.inf(&p2) - margin, p1.sup(&p2) + margin) {
+            return;
+        }
         self.reserve(params.idx_count(), params.vtx_count());
 
         let mut d = p2 - p1;
         d.try_normalize_mut(0.);
         d.scale_mut(params.half_thickness);
 
-        let v0 = self.push_vert(Vert {
-            pos: Vector4::new(p1.x + d.y, p1.y - d.x, 0., 1.),
-            col,
-        });
-        let v1 = self.push_vert(Vert {
-            pos: Vector4::new(p2.x + d.y, p2.y - d.x, 0., 1.),
-            col,
-        });
-        let v2 = self.push_vert(Vert {
-            pos: Vector4::new(p2.x - d.y, p2.y + d.x, 0., 1.),
-            col,
-        });
-        let v3 = self.push_vert(Vert {
-            pos: Vector4::new(p1.x - d.y, p1.y + d.x, 0., 1.),
-            col,
-        });
+        let v0 = self.push_vert(Vert::new(Vector4::new(p1.x + d.y, p1.y - d.x, 0., 1.), col));
+        let v1 = self.push_vert(Vert::new(Vector4::new(p2.x + d.y, p2.y - d.x, 0., 1.), col));
+        let v2 = self.push_vert(Vert::new(Vector4::new(p2.x - d.y, p2.y + d.x, 0., 1.), col));
+        let v3 = self.push_vert(Vert::new(Vector4::new(p1.x - d.y, p1.y + d.x, 0., 1.), col));
         self.push_elem(v0, v1, v2);
         self.push_elem(v0, v2, v3);
 
@@ -77,19 +209,13 @@ impl DrawList {
             for r in params.cap_segments.iter() {
                 if r.perp(&horizon) < 0. {
                     let xy = p1 + r;
-                    let v = self.push_vert(Vert {
-                        pos: Vector4::new(xy.x, xy.y, 0., 1.),
-                        col,
-                    });
+                    let v = self.push_vert(Vert::new(Vector4::new(xy.x, xy.y, 0., 1.), col));
                     self.push_elem(v0, v_b, v);
                     v_b = v;
                     v_t = v1;
                 } else {
                     let xy = p2 + r;
-                    let v = self.push_vert(Vert {
-                        pos: Vector4::new(xy.x, xy.y, 0., 1.),
-                        col,
-                    });
+                    let v = self.push_vert(Vert::new(Vector4::new(xy.x, xy.y, 0., 1.), col));
                     self.push_elem(v_t, v2, v);
                     v_t = v;
                     v_b = v3;
@@ -98,63 +224,60 @@ impl DrawList {
         }
     }
 
-    pub fn add_line(&mut self, ctx: &DrawContext, p1: Vector2<f32>, p2: Vector2<f32>, col: Color, thickness: f32) {
-        let resolution = thickness * ctx.scale;
-        let cap_segment_count = if resolution <= 1.0 {
-            0
-        } else {
-            (resolution * 1.5).ceil() as usize
-        };
+    pub fn add_line(
+        &mut self,
+        ctx: &DrawContext,
+        p1: Vector2<f32>,
+        p2: Vector2<f32>,
+        col: Color,
+        thickness: f32,
+    ) {
+        let margin = Vector2::repeat(thickness * 0.5);
+        if !self.is_visible(ctx, p1.inf(&p2) - margin, p1.sup(&p2) + margin) {
+            return;
+        }
+        if self.use_sdf_shapes() {
+            self.add_sdf_shape(ctx, SdfShape::capsule(p1, p2, thickness * 0.5, col));
+            return;
+        }
+        let half_thickness = thickness * 0.5;
+        let cap_segments =
+            if thickness * ctx.scale * ctx.pixel_ratio * self.transform_scale() <= 1.0 {
+                None
+            } else {
+                Some(self.unit_circle(ctx, half_thickness))
+            };
+        let cap_segment_count = cap_segments.as_ref().map_or(0, |c| c.len());
         let vtx_count = 4 + cap_segment_count;
         let idx_count = (2 + cap_segment_count) * 3;
         self.reserve(idx_count, vtx_count);
 
-        let half_thickness = thickness * 0.5;
         let mut d = p2 - p1;
         d.try_normalize_mut(0.);
         d.scale_mut(half_thickness);
 
-        let v0 = self.push_vert(Vert {
-            pos: Vector4::new(p1.x + d.y, p1.y - d.x, 0., 1.),
-            col,
-        });
-        let v1 = self.push_vert(Vert {
-            pos: Vector4::new(p2.x + d.y, p2.y - d.x, 0., 1.),
-            col,
-        });
-        let v2 = self.push_vert(Vert {
-            pos: Vector4::new(p2.x - d.y, p2.y + d.x, 0., 1.),
-            col,
-        });
-        let v3 = self.push_vert(Vert {
-            pos: Vector4::new(p1.x - d.y, p1.y + d.x, 0., 1.),
-            col,
-        });
+        let v0 = self.push_vert(Vert::new(Vector4::new(p1.x + d.y, p1.y - d.x, 0., 1.), col));
+        let v1 = self.push_vert(Vert::new(Vector4::new(p2.x + d.y, p2.y - d.x, 0., 1.), col));
+        let v2 = self.push_vert(Vert::new(Vector4::new(p2.x - d.y, p2.y + d.x, 0., 1.), col));
+        let v3 = self.push_vert(Vert::new(Vector4::new(p1.x - d.y, p1.y + d.x, 0., 1.), col));
         self.push_elem(v0, v1, v2);
         self.push_elem(v0, v2, v3);
 
-        if cap_segment_count > 0 {
+        if let Some(cap_segments) = cap_segments {
             let mut v_t = v1;
             let mut v_b = v3;
             let horizon = Vector2::new(-d.y, d.x);
-            for i in 0..=cap_segment_count {
-                let rad = i as f32 * 2.0 / cap_segment_count as f32 * std::f32::consts::PI;
-                let r = Vector2::new(rad.cos(), rad.sin()).scale(half_thickness);
+            for v in cap_segments.iter() {
+                let r = v.scale(half_thickness);
                 if r.perp(&horizon) < 0. {
                     let xy = p1 + r;
-                    let v = self.push_vert(Vert {
-                        pos: Vector4::new(xy.x, xy.y, 0., 1.),
-                        col,
-                    });
+                    let v = self.push_vert(Vert::new(Vector4::new(xy.x, xy.y, 0., 1.), col));
                     self.push_elem(v0, v_b, v);
                     v_b = v;
                     v_t = v1;
                 } else {
                     let xy = p2 + r;
-                    let v = self.push_vert(Vert {
-                        pos: Vector4::new(xy.x, xy.y, 0., 1.),
-                        col,
-                    });
+                    let v = self.push_vert(Vert::new(Vector4::new(xy.x, xy.y, 0., 1.), col));
                     self.push_elem(v_t, v2, v);
                     v_t = v;
                     v_b = v3;
@@ -163,37 +286,38 @@ impl DrawList {
         }
     }
 
-    pub fn add_circle(&mut self, ctx: &DrawContext, p: Vector2<f32>, r: f32, col: Color, thickness: f32) {
-        let resolution = thickness * ctx.scale;
+    pub fn add_circle(
+        &mut self,
+        ctx: &DrawContext,
+        p: Vector2<f32>,
+        r: f32,
+        col: Color,
+        thickness: f32,
+    ) {
+        let extent = Vector2::repeat(r + thickness * 0.5);
+        if !self.is_visible(ctx, p - extent, p + extent) {
+            return;
+        }
+        if self.use_sdf_shapes() {
+            self.add_sdf_shape(ctx, SdfShape::circle(p, r, col).with_thickness(thickness));
+            return;
+        }
         let half_thickness = thickness * 0.5;
-        let segment_count = (r + resolution).ceil() as usize;
+        let unit_circle = self.unit_circle(ctx, r + half_thickness);
+        let segment_count = unit_circle.len() - 1;
         let vtx_count = 2 * segment_count;
         let idx_count = (2 * segment_count) * 3;
         self.reserve(idx_count, vtx_count);
 
         let r_o = r + half_thickness;
         let r_i = r - half_thickness;
-        let mut v_o0 = self.push_vert(Vert {
-            pos: Vector4::new(p.x + r_o, p.y, 0., 1.),
-            col,
-        });
-        let mut v_i0 = self.push_vert(Vert {
-            pos: Vector4::new(p.x + r_i, p.y, 0., 1.),
-            col,
-        });
-        for i in 1..=segment_count {
-            let rad = i as f32 * 2.0 / segment_count as f32 * std::f32::consts::PI;
-            let v = Vector2::new(rad.cos(), rad.sin());
+        let mut v_o0 = self.push_vert(Vert::new(Vector4::new(p.x + r_o, p.y, 0., 1.), col));
+        let mut v_i0 = self.push_vert(Vert::new(Vector4::new(p.x + r_i, p.y, 0., 1.), col));
+        for v in unit_circle[1..].iter() {
             let xy_o = p + v.scale(r_o);
-            let v_o1 = self.push_vert(Vert {
-                pos: Vector4::new(xy_o.x, xy_o.y, 0., 1.),
-                col,
-            });
+            let v_o1 = self.push_vert(Vert::new(Vector4::new(xy_o.x, xy_o.y, 0., 1.), col));
             let xy_i = p + v.scale(r_i);
-            let v_i1 = self.push_vert(Vert {
-                pos: Vector4::new(xy_i.x, xy_i.y, 0., 1.),
-                col,
-            });
+            let v_i1 = self.push_vert(Vert::new(Vector4::new(xy_i.x, xy_i.y, 0., 1.), col));
             self.push_elem(v_o0, v_i0, v_o1);
             self.push_elem(v_o1, v_i1, v_i0);
             v_o0 = v_o1;
@@ -204,24 +328,273 @@ impl DrawList {
     #[allow(clippy::many_single_char_names)]
     pub fn add_square(&mut self, p: Vector2<f32>, size: f32, col: Color) {
         let half_size = size * 0.5;
+        if !self.is_inside_clip(p.add_scalar(-half_size), p.add_scalar(half_size)) {
+            return;
+        }
+        if self.use_sdf_shapes() {
+            self.push_sdf_shape(SdfShape::rect(
+                p,
+                Vector2::new(half_size, half_size),
+                [0.; 4],
+                col,
+            ));
+            return;
+        }
         self.reserve(6, 4);
-        let a = self.push_vert(Vert {
-            pos: Vector4::new(p.x - half_size, p.y - half_size, 0., 1.),
+        let a = self.push_vert(Vert::new(
+            Vector4::new(p.x - half_size, p.y - half_size, 0., 1.),
             col,
-        });
-        let b = self.push_vert(Vert {
-            pos: Vector4::new(p.x + half_size, p.y - half_size, 0., 1.),
+        ));
+        let b = self.push_vert(Vert::new(
+            Vector4::new(p.x + half_size, p.y - half_size, 0., 1.),
             col,
-        });
-        let c = self.push_vert(Vert {
-            pos: Vector4::new(p.x - half_size, p.y + half_size, 0., 1.),
+        ));
+        let c = self.push_vert(Vert::new(
+            Vector4::new(p.x - half_size, p.y + half_size, 0., 1.),
             col,
-        });
-        let d = self.push_vert(Vert {
-            pos: Vector4::new(p.x + half_size, p.y + half_size, 0., 1.),
+        ));
+        let d = self.push_vert(Vert::new(
+            Vector4::new(p.x + half_size, p.y + half_size, 0., 1.),
             col,
-        });
+        ));
         self.push_elem(a, b, c);
         self.push_elem(b, c, d);
     }
+
+    // Triangulates as a fan around the first point, so `points` must describe a convex polygon.
+    pub fn add_convex_polygon(&mut self, ctx: &DrawContext, points: &[Vector2<f32>], col: Color) {
+        let (min, max) = points_bounds(points);
+        if points.len() < 3 || !self.is_visible(ctx, min, max) {
+            return;
+        }
+        self.reserve((points.len() - 2) * 3, points.len());
+        let first = self.push_vert(Vert::new(
+            Vector4::new(points[0].x, points[0].y, 0., 1.),
+            col,
+        ));
+        let mut prev = self.push_vert(Vert::new(
+            Vector4::new(points[1].x, points[1].y, 0., 1.),
+            col,
+        ));
+        for xy in &points[2..] {
+            let v = self.push_vert(Vert::new(Vector4::new(xy.x, xy.y, 0., 1.), col));
+            self.push_elem(first, prev, v);
+            prev = v;
+        }
+    }
+
+    fn add_closed_outline(
+        &mut self,
+        ctx: &DrawContext,
+        points: Vec<Vector2<f32>>,
+        col: Color,
+        thickness: f32,
+    ) {
+        let polyline = Polyline {
+            points,
+            closed: true,
+        };
+        self.stroke_polyline(ctx, &polyline, col, &StrokeStyle::new(thickness));
+    }
+
+    pub fn add_rect(&mut self, ctx: &DrawContext, p: Vector2<f32>, size: Vector2<f32>, col: Color) {
+        let half_size = size * 0.5;
+        if self.use_sdf_shapes() {
+            self.add_sdf_shape(ctx, SdfShape::rect(p, half_size, [0.; 4], col));
+            return;
+        }
+        self.add_convex_polygon(
+            ctx,
+            &[
+                p - half_size,
+                p + Vector2::new(half_size.x, -half_size.y),
+                p + half_size,
+                p + Vector2::new(-half_size.x, half_size.y),
+            ],
+            col,
+        );
+    }
+
+    pub fn add_rect_outline(
+        &mut self,
+        ctx: &DrawContext,
+        p: Vector2<f32>,
+        size: Vector2<f32>,
+        col: Color,
+        thickness: f32,
+    ) {
+        let half_size = size * 0.5;
+        if self.use_sdf_shapes() {
+            self.add_sdf_shape(
+                ctx,
+                SdfShape::rect(p, half_size, [0.; 4], col).with_thickness(thickness),
+            );
+            return;
+        }
+        let points = vec![
+            p - half_size,
+            p + Vector2::new(half_size.x, -half_size.y),
+            p + half_size,
+            p + Vector2::new(-half_size.x, half_size.y),
+        ];
+        self.add_closed_outline(ctx, points, col, thickness);
+    }
+
+    // `radii` go clockwise from the top-left corner and are scaled down together when they
+    // don't fit.
+    pub fn add_rounded_rect(
+        &mut self,
+        ctx: &DrawContext,
+        p: Vector2<f32>,
+        size: Vector2<f32>,
+        radii: [f32; 4],
+        col: Color,
+    ) {
+        if self.use_sdf_shapes() {
+            self.add_sdf_shape(
+                ctx,
+                SdfShape::rect(p, size * 0.5, fit_radii(size, radii), col),
+            );
+            return;
+        }
+        let points = rounded_rect_points(ctx, self.transform_scale(), p, size, radii);
+        self.add_convex_polygon(ctx, &points, col);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_rounded_rect_outline(
+        &mut self,
+        ctx: &DrawContext,
+        p: Vector2<f32>,
+        size: Vector2<f32>,
+        radii: [f32; 4],
+        col: Color,
+        thickness: f32,
+    ) {
+        if self.use_sdf_shapes() {
+            let shape = SdfShape::rect(p, size * 0.5, fit_radii(size, radii), col);
+            self.add_sdf_shape(ctx, shape.with_thickness(thickness));
+            return;
+        }
+        let points = rounded_rect_points(ctx, self.transform_scale(), p, size, radii);
+        self.add_closed_outline(ctx, points, col, thickness);
+    }
+
+    pub fn add_disc(&mut self, ctx: &DrawContext, p: Vector2<f32>, r: f32, col: Color) {
+        if self.use_sdf_shapes() {
+            self.add_sdf_shape(ctx, SdfShape::circle(p, r, col));
+            return;
+        }
+        self.add_ellipse(ctx, p, Vector2::new(r, r), col);
+    }
+
+    pub fn add_ellipse(
+        &mut self,
+        ctx: &DrawContext,
+        p: Vector2<f32>,
+        radii: Vector2<f32>,
+        col: Color,
+    ) {
+        let points = self.ellipse_points(ctx, p, radii);
+        self.add_convex_polygon(ctx, &points, col);
+    }
+
+    pub fn add_ellipse_outline(
+        &mut self,
+        ctx: &DrawContext,
+        p: Vector2<f32>,
+        radii: Vector2<f32>,
+        col: Color,
+        thickness: f32,
+    ) {
+        let points = self.ellipse_points(ctx, p, radii);
+        self.add_closed_outline(ctx, points, col, thickness);
+    }
+
+    // A pie slice when `inner_radius` is zero, an annulus sector otherwise.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_sector(
+        &mut self,
+        ctx: &DrawContext,
+        p: Vector2<f32>,
+        inner_radius: f32,
+        outer_radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        col: Color,
+    ) {
+        let extent = Vector2::repeat(outer_radius);
+        if !self.is_visible(ctx, p - extent, p + extent) {
+            return;
+        }
+        let segment_count = ctx.arc_segment_count(
+            outer_radius * self.transform_scale(),
+            end_angle - start_angle,
+        );
+        let mut outer = Vec::with_capacity(segment_count + 1);
+        push_arc(
+            &mut outer,
+            p,
+            Vector2::new(outer_radius, outer_radius),
+            start_angle,
+            end_angle,
+            segment_count,
+        );
+        if inner_radius <= 0. {
+            self.reserve(segment_count * 3, segment_count + 2);
+            let c = self.push_vert(Vert::new(Vector4::new(p.x, p.y, 0., 1.), col));
+            let mut v_o0 =
+                self.push_vert(Vert::new(Vector4::new(outer[0].x, outer[0].y, 0., 1.), col));
+            for xy in &outer[1..] {
+                let v_o1 = self.push_vert(Vert::new(Vector4::new(xy.x, xy.y, 0., 1.), col));
+                self.push_elem(c, v_o0, v_o1);
+                v_o0 = v_o1;
+            }
+            return;
+        }
+
+        let mut inner = Vec::with_capacity(segment_count + 1);
+        push_arc(
+            &mut inner,
+            p,
+            Vector2::new(inner_radius, inner_radius),
+            start_angle,
+            end_angle,
+            segment_count,
+        );
+        self.reserve(segment_count * 6, (segment_count + 1) * 2);
+        let mut v_o0 = self.push_vert(Vert::new(Vector4::new(outer[0].x, outer[0].y, 0., 1.), col));
+        let mut v_i0 = self.push_vert(Vert::new(Vector4::new(inner[0].x, inner[0].y, 0., 1.), col));
+        for (xy_o, xy_i) in outer[1..].iter().zip(&inner[1..]) {
+            let v_o1 = self.push_vert(Vert::new(Vector4::new(xy_o.x, xy_o.y, 0., 1.), col));
+            let v_i1 = self.push_vert(Vert::new(Vector4::new(xy_i.x, xy_i.y, 0., 1.), col));
+            self.push_elem(v_o0, v_i0, v_o1);
+            self.push_elem(v_o1, v_i1, v_i0);
+            v_o0 = v_o1;
+            v_i0 = v_i1;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_arc(
+        &mut self,
+        ctx: &DrawContext,
+        p: Vector2<f32>,
+        r: f32,
+        start_angle: f32,
+        end_angle: f32,
+        col: Color,
+        thickness: f32,
+    ) {
+        let half_thickness = thickness * 0.5;
+        self.add_sector(
+            ctx,
+            p,
+            (r - half_thickness).max(0.),
+            r + half_thickness,
+            start_angle,
+            end_angle,
+            col,
+        );
+    }
 }
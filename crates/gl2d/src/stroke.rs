@@ -0,0 +1,481 @@
+use nalgebra::{Affine2, Vector2, Vector4};
+
+use crate::draw_list::{
+    arc_segment_count, points_bounds, BlendMode, Color, DrawContext, DrawList, FillRule, Vert,
+};
+use crate::glyph::GlyphBuilder;
+use crate::path::{Path, Polyline};
+
+// Dashes of one polyline past this many are stroked solid, which patterns that dense look like
+// anyway.
+const MAX_DASHES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    Butt,
+    Square,
+    Round,
+}
+
+#[derive(Debug, Clone)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    pub miter_limit: f32,
+    pub dashes: Vec<f32>,
+    pub dash_offset: f32,
+}
+
+impl StrokeStyle {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 10.,
+            dashes: vec![],
+            dash_offset: 0.,
+        }
+    }
+}
+
+// Moves `distance` along `pattern`, from dash `index` with `remaining` of it left.
+fn advance_dash(
+    pattern: &[f32],
+    total: f32,
+    index: &mut usize,
+    remaining: &mut f32,
+    distance: f32,
+) {
+    let mut distance = distance % total;
+    while distance > 0. {
+        if distance < *remaining {
+            *remaining -= distance;
+            return;
+        }
+        distance -= *remaining;
+        *index = (*index + 1) % pattern.len();
+        *remaining = pattern[*index];
+    }
+}
+
+// Each dash comes with the direction of the path where it ends, which orients the caps of
+// zero-length dashes. Segments for which `is_visible` fails on their bounds get no dashes, so
+// it must allow for the joins and caps at their ends.
+fn apply_dashes(
+    polyline: &Polyline,
+    dashes: &[f32],
+    dash_offset: f32,
+    is_visible: impl Fn(Vector2<f32>, Vector2<f32>) -> bool,
+) -> Vec<(Polyline, Vector2<f32>)> {
+    let mut pattern = dashes.to_vec();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_slice(dashes);
+    }
+    let total: f32 = pattern.iter().sum();
+    if total <= 0. || pattern.iter().any(|&d| d < 0.) {
+        return vec![(polyline.clone(), Vector2::new(1., 0.))];
+    }
+
+    let mut index = 0;
+    let mut remaining = pattern[0];
+    let offset = dash_offset.rem_euclid(total);
+    advance_dash(&pattern, total, &mut index, &mut remaining, offset);
+
+    let mut points = polyline.points.clone();
+    if polyline.closed {
+        points.push(points[0]);
+    }
+    // dash boundaries falling on vertices would repeat them
+    fn push_point(polyline: &mut Polyline, p: Vector2<f32>) {
+        if polyline.points.last() != Some(&p) {
+            polyline.points.push(p);
+        }
+    }
+    let mut dashed = vec![];
+    let mut current = Polyline::default();
+    let mut dir = Vector2::new(1., 0.);
+    let mut solid = false;
+    if index % 2 == 0 {
+        current.points.push(points[0]);
+    }
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let length = (b - a).norm();
+        if length == 0. {
+            continue;
+        }
+        dir = (b - a) / length;
+        if solid {
+            push_point(&mut current, b);
+            continue;
+        }
+        if !is_visible(a.inf(&b), a.sup(&b)) {
+            // the dash ends and restarts out of view, with the pattern carrying on
+            if current.points.len() > 1 {
+                dashed.push((std::mem::take(&mut current), dir));
+            }
+            current.points.clear();
+            advance_dash(&pattern, total, &mut index, &mut remaining, length);
+            if index % 2 == 0 {
+                current.points.push(b);
+            }
+            continue;
+        }
+        let mut t = 0.;
+        while length - t > remaining {
+            t += remaining;
+            let p = a + (b - a) * (t / length);
+            push_point(&mut current, p);
+            if index % 2 == 0 {
+                dashed.push((std::mem::take(&mut current), dir));
+                if dashed.len() == MAX_DASHES {
+                    solid = true;
+                    current.points.push(p);
+                    break;
+                }
+            }
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+        remaining -= length - t;
+        if solid || index % 2 == 0 {
+            push_point(&mut current, b);
+        }
+    }
+    if current.points.len() > 1 {
+        dashed.push((current, dir));
+    }
+    dashed
+}
+
+struct Stroker<'a> {
+    draw_list: &'a mut DrawList,
+    // collects the triangles instead of the draw list, for strokes that can't blend twice
+    coverage: Option<GlyphBuilder>,
+    style: &'a StrokeStyle,
+    col: Color,
+    half_width: f32,
+    tolerance: f32,
+}
+
+impl<'a> Stroker<'a> {
+    fn vert(&mut self, p: Vector2<f32>) -> u32 {
+        self.draw_list
+            .push_vert(Vert::new(Vector4::new(p.x, p.y, 0., 1.), self.col))
+    }
+
+    fn triangle(&mut self, a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>) {
+        if let Some(coverage) = &mut self.coverage {
+            coverage.add_coverage_triangle(a, b, c);
+            return;
+        }
+        let a = self.vert(a);
+        let b = self.vert(b);
+        let c = self.vert(c);
+        self.draw_list.push_elem(a, b, c);
+    }
+
+    fn quad(&mut self, a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>, d: Vector2<f32>) {
+        if self.coverage.is_some() {
+            self.triangle(a, b, c);
+            self.triangle(a, c, d);
+            return;
+        }
+        let a = self.vert(a);
+        let b = self.vert(b);
+        let c = self.vert(c);
+        let d = self.vert(d);
+        self.draw_list.push_elem(a, b, c);
+        self.draw_list.push_elem(a, c, d);
+    }
+
+    fn fan(&mut self, center: Vector2<f32>, from: Vector2<f32>, angle: f32) {
+        let segment_count = arc_segment_count(self.half_width, angle, self.tolerance);
+        let step = angle / segment_count as f32;
+        let rim = (0..=segment_count).map(|i| {
+            let (sin, cos) = (step * i as f32).sin_cos();
+            center + Vector2::new(from.x * cos - from.y * sin, from.x * sin + from.y * cos)
+        });
+        if self.coverage.is_some() {
+            let rim: Vec<_> = rim.collect();
+            for pair in rim.windows(2) {
+                self.triangle(center, pair[0], pair[1]);
+            }
+            return;
+        }
+        let c = self.vert(center);
+        let mut prev = None;
+        for p in rim {
+            let v = self.vert(p);
+            if let Some(prev) = prev {
+                self.draw_list.push_elem(c, prev, v);
+            }
+            prev = Some(v);
+        }
+    }
+
+    fn join(&mut self, p: Vector2<f32>, d0: Vector2<f32>, d1: Vector2<f32>) {
+        let cross = d0.perp(&d1);
+        if cross.abs() < 1e-6 && d0.dot(&d1) > 0. {
+            return;
+        }
+        // the outer side of the turn is opposite to the direction we turn towards
+        let side = if cross > 0. { -1. } else { 1. };
+        let n0 = Vector2::new(-d0.y, d0.x) * (self.half_width * side);
+        let n1 = Vector2::new(-d1.y, d1.x) * (self.half_width * side);
+        match self.style.join {
+            LineJoin::Bevel => self.triangle(p, p + n0, p + n1),
+            LineJoin::Round => {
+                let angle = n0.perp(&n1).atan2(n0.dot(&n1));
+                self.fan(p, n0, angle);
+            }
+            LineJoin::Miter => {
+                let cos_half = ((1. + d0.dot(&d1)) * 0.5).max(0.).sqrt();
+                if cos_half > 0. && 1. / cos_half <= self.style.miter_limit {
+                    let tip = p + (n0 + n1).normalize() * (self.half_width / cos_half);
+                    self.quad(p, p + n0, tip, p + n1);
+                } else {
+                    self.triangle(p, p + n0, p + n1);
+                }
+            }
+        }
+    }
+
+    fn cap(&mut self, p: Vector2<f32>, d: Vector2<f32>) {
+        let n = Vector2::new(-d.y, d.x) * self.half_width;
+        match self.style.cap {
+            LineCap::Butt => {}
+            LineCap::Square => {
+                let e = d * self.half_width;
+                self.quad(p + n, p + n + e, p - n + e, p - n);
+            }
+            LineCap::Round => self.fan(p, n, -std::f32::consts::PI),
+        }
+    }
+
+    // A polyline that collapses to a single point only gets its caps, oriented along `dot_dir`.
+    fn stroke(&mut self, polyline: &Polyline, dot_dir: Vector2<f32>) {
+        let mut points = polyline.points.clone();
+        points.dedup();
+        if polyline.closed && points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        match points.len() {
+            0 => return,
+            1 => {
+                self.cap(points[0], -dot_dir);
+                self.cap(points[0], dot_dir);
+                return;
+            }
+            _ => {}
+        }
+        let mut dirs: Vec<Vector2<f32>> = points
+            .windows(2)
+            .map(|segment| (segment[1] - segment[0]).normalize())
+            .collect();
+        if polyline.closed {
+            dirs.push((points[0] - points[points.len() - 1]).normalize());
+        }
+        for (i, d) in dirs.iter().enumerate() {
+            let a = points[i];
+            let b = points[(i + 1) % points.len()];
+            let n = Vector2::new(-d.y, d.x) * self.half_width;
+            self.quad(a + n, b + n, b - n, a - n);
+        }
+        for i in 1..dirs.len() {
+            self.join(points[i], dirs[i - 1], dirs[i]);
+        }
+        if polyline.closed {
+            self.join(points[0], dirs[dirs.len() - 1], dirs[0]);
+        } else {
+            self.cap(points[0], -dirs[0]);
+            self.cap(points[points.len() - 1], dirs[dirs.len() - 1]);
+        }
+    }
+}
+
+impl DrawList {
+    // Segments, joins and caps overlap, so strokes that would show it, translucent ones or ones
+    // drawn with a blend mode other than normal, go through a coverage pass like fills.
+    pub fn stroke_polyline(
+        &mut self,
+        ctx: &DrawContext,
+        polyline: &Polyline,
+        col: Color,
+        style: &StrokeStyle,
+    ) {
+        // far enough for miter tips and square caps
+        let margin = style.width * 0.5 * style.miter_limit.max(2.);
+        let (min, max) = points_bounds(&polyline.points);
+        if !self.is_visible(ctx, min.add_scalar(-margin), max.add_scalar(margin)) {
+            return;
+        }
+        let dashes = if style.dashes.is_empty() {
+            vec![]
+        } else {
+            apply_dashes(polyline, &style.dashes, style.dash_offset, |min, max| {
+                self.is_visible(ctx, min.add_scalar(-margin), max.add_scalar(margin))
+            })
+        };
+        let overlaps_show = col.w < 1.
+            || !matches!(
+                self.blend_mode,
+                BlendMode::Normal | BlendMode::PremultipliedNormal
+            );
+        let tolerance = self.tolerance(ctx);
+        let mut stroker = Stroker {
+            draw_list: self,
+            coverage: if overlaps_show {
+                Some(GlyphBuilder::new(tolerance))
+            } else {
+                None
+            },
+            style,
+            col,
+            half_width: style.width * 0.5,
+            tolerance,
+        };
+        if style.dashes.is_empty() {
+            stroker.stroke(polyline, Vector2::new(1., 0.));
+        } else {
+            for (dash, dir) in &dashes {
+                stroker.stroke(dash, *dir);
+            }
+        }
+        if let Some(coverage) = stroker.coverage.take() {
+            self.new_text_layer_with_fill_rule(col, FillRule::NonZero);
+            self.add_glyph(ctx, &Affine2::identity(), &coverage.build());
+            self.new_layer();
+        }
+    }
+
+    pub fn stroke_path(&mut self, ctx: &DrawContext, path: &Path, col: Color, style: &StrokeStyle) {
+        let tolerance = self.tolerance(ctx);
+        for polyline in path.flatten(tolerance) {
+            self.stroke_polyline(ctx, &polyline, col, style);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(points: &[(f32, f32)]) -> Polyline {
+        Polyline {
+            points: points.iter().map(|&(x, y)| Vector2::new(x, y)).collect(),
+            closed: false,
+        }
+    }
+
+    fn dash_ends(dashes: &[(Polyline, Vector2<f32>)]) -> Vec<(f32, f32)> {
+        dashes
+            .iter()
+            .map(|(dash, _)| (dash.points[0].x, dash.points.last().unwrap().x))
+            .collect()
+    }
+
+    #[test]
+    fn dashes_follow_the_pattern() {
+        let polyline = line(&[(0., 0.), (10., 0.)]);
+        let dashes = apply_dashes(&polyline, &[3., 1.], 0., |_, _| true);
+        assert_eq!(dash_ends(&dashes), [(0., 3.), (4., 7.), (8., 10.)]);
+        let dashes = apply_dashes(&polyline, &[3., 1.], 2., |_, _| true);
+        assert_eq!(dash_ends(&dashes), [(0., 1.), (2., 5.), (6., 9.)]);
+        // odd patterns repeat once to alternate dashes and gaps
+        let dashes = apply_dashes(&polyline, &[2.], 0., |_, _| true);
+        assert_eq!(dash_ends(&dashes), [(0., 2.), (4., 6.), (8., 10.)]);
+    }
+
+    #[test]
+    fn zero_length_dashes_keep_the_path_direction() {
+        let polyline = line(&[(0., 0.), (10., 0.), (10., 10.), (10., 10.)]);
+        let dashes = apply_dashes(&polyline, &[0., 5.], 0., |_, _| true);
+        let dots: Vec<_> = dashes
+            .iter()
+            .map(|(dash, dir)| (dash.points[0], *dir))
+            .collect();
+        assert!(dashes.iter().all(|(dash, _)| dash.points.len() == 1));
+        assert_eq!(dots.len(), 4);
+        assert_eq!(dots[1], (Vector2::new(5., 0.), Vector2::new(1., 0.)));
+        assert_eq!(dots[3], (Vector2::new(10., 5.), Vector2::new(0., 1.)));
+
+        // dots are round caps only, with nothing degenerate in between
+        let ctx = DrawContext::new(Vector2::new(100, 100));
+        let mut draw_list = DrawList::new();
+        let mut style = StrokeStyle::new(2.);
+        style.cap = LineCap::Round;
+        style.dashes = vec![0., 5.];
+        draw_list.stroke_polyline(&ctx, &polyline, Color::new(1., 0., 0., 1.), &style);
+        assert!(!draw_list.indices().is_empty());
+        assert!(draw_list
+            .vtx_buffer
+            .iter()
+            .all(|vert| vert.pos.iter().all(|x| x.is_finite())));
+    }
+
+    #[test]
+    fn dashes_out_of_view_are_skipped() {
+        let polyline = line(&[(0., 0.), (10., 0.), (1000., 0.), (1010., 0.)]);
+        let dashes = apply_dashes(&polyline, &[3., 1.], 0., |_, max| max.x <= 100.);
+        assert_eq!(dash_ends(&dashes), [(0., 3.), (4., 7.), (8., 10.)]);
+    }
+
+    #[test]
+    fn dense_dashes_end_solid() {
+        let polyline = line(&[(0., 0.), (1000., 0.)]);
+        let dashes = apply_dashes(&polyline, &[0.01, 0.01], 0., |_, _| true);
+        assert_eq!(dashes.len(), MAX_DASHES + 1);
+        assert_eq!(dashes.last().unwrap().0.points.last().unwrap().x, 1000.);
+    }
+
+    fn stroked_elems(polyline: &Polyline, style: &StrokeStyle) -> usize {
+        let ctx = DrawContext::new(Vector2::new(100, 100));
+        let mut draw_list = DrawList::new();
+        draw_list.stroke_polyline(&ctx, polyline, Color::new(1., 0., 0., 1.), style);
+        draw_list.cmds.iter().map(|cmd| cmd.num_of_elems).sum()
+    }
+
+    #[test]
+    fn joins() {
+        let corner = line(&[(10., 10.), (50., 10.), (50., 50.)]);
+        let mut style = StrokeStyle::new(4.);
+        // two segment quads and the miter quad
+        assert_eq!(stroked_elems(&corner, &style), 6);
+        style.join = LineJoin::Bevel;
+        assert_eq!(stroked_elems(&corner, &style), 5);
+        style.join = LineJoin::Round;
+        assert!(stroked_elems(&corner, &style) > 5);
+
+        // past the miter limit, miters fall back to bevels
+        let spike = line(&[(10., 10.), (50., 10.), (10., 11.)]);
+        style.join = LineJoin::Miter;
+        assert_eq!(stroked_elems(&spike, &style), 5);
+        // straight continuations have no join
+        let straight = line(&[(10., 10.), (30., 10.), (50., 10.)]);
+        assert_eq!(stroked_elems(&straight, &style), 4);
+    }
+
+    #[test]
+    fn translucent_strokes_cover_once() {
+        let ctx = DrawContext::new(Vector2::new(100, 100));
+        let mut draw_list = DrawList::new();
+        let corner = line(&[(10., 10.), (50., 10.), (50., 50.)]);
+        let style = StrokeStyle::new(4.);
+        draw_list.stroke_polyline(&ctx, &corner, Color::new(1., 0., 0., 0.5), &style);
+        let text_layers: Vec<_> = draw_list.cmds.iter().filter(|cmd| cmd.is_text).collect();
+        assert_eq!(text_layers.len(), 1);
+        assert_eq!(text_layers[0].fill_rule, FillRule::NonZero);
+        // the cover quad and the six triangles of the stroke
+        assert_eq!(text_layers[0].num_of_elems, 8);
+    }
+}
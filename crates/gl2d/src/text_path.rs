@@ -0,0 +1,161 @@
+use nalgebra::{Affine2, Matrix3, Vector2};
+
+use crate::draw_list::{
+    cubic_segment_count, quad_segment_count, Color, DrawContext, DrawList, DEFAULT_TOLERANCE,
+};
+use crate::glyph::GlyphCache;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign {
+    Start,
+    Center,
+    End,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextPath {
+    points: Vec<Vector2<f32>>,
+    distances: Vec<f32>,
+    tolerance: f32,
+}
+
+impl TextPath {
+    pub fn new(start: Vector2<f32>) -> Self {
+        Self {
+            points: vec![start],
+            distances: vec![0.],
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    // Maximum distance between the curves added afterwards and the segments approximating them,
+    // e.g. `DrawList::tolerance`.
+    pub fn set_tolerance(&mut self, tolerance: f32) -> &mut Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    // `None` for an empty slice, which has no start point.
+    pub fn from_polyline(points: &[Vector2<f32>]) -> Option<Self> {
+        let (&start, rest) = points.split_first()?;
+        let mut path = Self::new(start);
+        for &p in rest {
+            path.line_to(p);
+        }
+        Some(path)
+    }
+
+    pub fn line_to(&mut self, p: Vector2<f32>) -> &mut Self {
+        let last = *self.points.last().unwrap();
+        let distance = (p - last).norm();
+        if distance > 0. {
+            self.points.push(p);
+            self.distances.push(self.length() + distance);
+        }
+        self
+    }
+
+    pub fn quad_to(&mut self, c: Vector2<f32>, p: Vector2<f32>) -> &mut Self {
+        let p0 = *self.points.last().unwrap();
+        let segment_count = quad_segment_count(p0, c, p, self.tolerance);
+        for i in 1..=segment_count {
+            let t = i as f32 / segment_count as f32;
+            let mt = 1. - t;
+            self.line_to(p0 * (mt * mt) + c * (2. * mt * t) + p * (t * t));
+        }
+        self
+    }
+
+    pub fn cubic_to(&mut self, c1: Vector2<f32>, c2: Vector2<f32>, p: Vector2<f32>) -> &mut Self {
+        let p0 = *self.points.last().unwrap();
+        let segment_count = cubic_segment_count(p0, c1, c2, p, self.tolerance);
+        for i in 1..=segment_count {
+            let t = i as f32 / segment_count as f32;
+            let mt = 1. - t;
+            self.line_to(
+                p0 * (mt * mt * mt)
+                    + c1 * (3. * mt * mt * t)
+                    + c2 * (3. * mt * t * t)
+                    + p * (t * t * t),
+            );
+        }
+        self
+    }
+
+    pub fn length(&self) -> f32 {
+        *self.distances.last().unwrap()
+    }
+
+    fn sample(&self, distance: f32) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        // also rejects NaN
+        if !(0. ..=self.length()).contains(&distance) || self.points.len() < 2 {
+            return None;
+        }
+        let i = match self.distances.binary_search_by(|d| d.total_cmp(&distance)) {
+            Ok(i) => i.max(1),
+            Err(i) => i,
+        };
+        let (a, b) = (self.points[i - 1], self.points[i]);
+        let (da, db) = (self.distances[i - 1], self.distances[i]);
+        let tangent = (b - a).unscale(db - da);
+        Some((a + tangent * (distance - da), tangent))
+    }
+
+    // Returns one transform per advance; glyphs falling off either end of the path get `None`.
+    pub fn layout(
+        &self,
+        advances: &[f32],
+        scale: f32,
+        align: TextAlign,
+        offset: Vector2<f32>,
+    ) -> Vec<Option<Affine2<f32>>> {
+        let run_length = advances.iter().sum::<f32>() * scale;
+        let start = match align {
+            TextAlign::Start => 0.,
+            TextAlign::Center => (self.length() - run_length) * 0.5,
+            TextAlign::End => self.length() - run_length,
+        } + offset.x;
+        let mut pen = start;
+        advances
+            .iter()
+            .map(|advance| {
+                let half_advance = advance * scale * 0.5;
+                let center = pen + half_advance;
+                pen += advance * scale;
+                let (p, tangent) = self.sample(center)?;
+                let normal = Vector2::new(-tangent.y, tangent.x);
+                let origin = p - tangent * half_advance + normal * offset.y;
+                #[rustfmt::skip]
+                let m = Matrix3::new(
+                    tangent.x * scale, normal.x * scale, origin.x,
+                    tangent.y * scale, normal.y * scale, origin.y,
+                    0., 0., 1.,
+                );
+                Some(Affine2::from_matrix_unchecked(m))
+            })
+            .collect()
+    }
+}
+
+impl DrawList {
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_glyph_run_on_path(
+        &mut self,
+        ctx: &DrawContext,
+        path: &TextPath,
+        glyph_cache: &GlyphCache,
+        glyphs: &[(usize, f32)],
+        scale: f32,
+        align: TextAlign,
+        offset: Vector2<f32>,
+        col: Color,
+    ) {
+        let advances: Vec<f32> = glyphs.iter().map(|&(_, advance)| advance).collect();
+        let transforms = path.layout(&advances, scale, align, offset);
+        for (&(glyph, _), transform) in glyphs.iter().zip(transforms) {
+            if let Some(transform) = transform {
+                self.add_glyph_instance(ctx, &transform, glyph_cache, glyph, col);
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use nalgebra::{Vector2, Vector4};
+
+use crate::draw_list::{Color, DrawList, Vert};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureId(pub(crate) u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureWrap {
+    Clamp,
+    Repeat,
+    MirroredRepeat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureOptions {
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    pub wrap: TextureWrap,
+    // generates the mip chain on the CPU whenever the texture is uploaded
+    pub mipmaps: bool,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            wrap: TextureWrap::Clamp,
+            mipmaps: false,
+        }
+    }
+}
+
+impl DrawList {
+    // Vertices pushed into a texture layer carry their UVs in `uv`.
+    pub fn new_texture_layer(&mut self, texture: TextureId) {
+        self.new_layer();
+        self.cmds.last_mut().unwrap().texture = Some(texture);
+    }
+
+    // Images share a layer with directly preceding images of the same texture; a fresh regular
+    // layer is opened afterwards for untextured content. `uv_rect` may go past [0, 1] either way
+    // to repeat the texture.
+    pub fn add_image(
+        &mut self,
+        texture: TextureId,
+        rect: (Vector2<f32>, Vector2<f32>),
+        uv_rect: (Vector2<f32>, Vector2<f32>),
+        tint: Color,
+    ) {
+        if !self.is_inside_clip(rect.0, rect.1) {
+            return;
+        }
+        let (uv_min, uv_max) = uv_rect;
+        let (uv_lo, uv_hi) = (uv_min.inf(&uv_max), uv_min.sup(&uv_max));
+        let len = self.cmds.len();
+        let reusable = len >= 2
+            && self.cmds[len - 1].is_empty()
+            && self.cmds[len - 2].texture == Some(texture)
+            && !self.cmds[len - 2].is_text
+            && self.cmds[len - 2].clip_rect == self.clip_rect()
+            && self.cmds[len - 2].clip_depth == self.layer_clip_depth()
+            && self.cmds[len - 2].blend_mode == self.blend_mode
+            && uv_lo >= self.cmds[len - 2].uv_offset
+            && uv_hi <= self.cmds[len - 2].uv_offset + self.cmds[len - 2].uv_scale;
+        if reusable {
+            self.cmds.pop();
+        } else {
+            self.new_texture_layer(texture);
+            let cmd = self.cmds.last_mut().unwrap();
+            // the first image of a layer packs its UVs exactly, as 0 or 1
+            cmd.uv_offset = uv_lo;
+            cmd.uv_scale = (uv_hi - uv_lo).map(|extent| if extent > 0. { extent } else { 1. });
+        }
+
+        let (min, max) = rect;
+        let cmd = self.cmds.last().unwrap();
+        let (uv_offset, uv_scale) = (cmd.uv_offset, cmd.uv_scale);
+        let pack = |uv: Vector2<f32>| (uv - uv_offset).component_div(&uv_scale);
+        let (uv_min, uv_max) = (pack(uv_min), pack(uv_max));
+        self.reserve(6, 4);
+        let a = self.push_vert(Vert::new(
+            Vector4::new(min.x, min.y, uv_min.x, uv_min.y),
+            tint,
+        ));
+        let b = self.push_vert(Vert::new(
+            Vector4::new(max.x, min.y, uv_max.x, uv_min.y),
+            tint,
+        ));
+        let c = self.push_vert(Vert::new(
+            Vector4::new(min.x, max.y, uv_min.x, uv_max.y),
+            tint,
+        ));
+        let d = self.push_vert(Vert::new(
+            Vector4::new(max.x, max.y, uv_max.x, uv_max.y),
+            tint,
+        ));
+        self.push_elem(a, b, c);
+        self.push_elem(b, c, d);
+        self.new_layer();
+    }
+}